@@ -0,0 +1,539 @@
+use chrono::Utc;
+
+use heroku_rs::endpoints::{apps, builds, custom, pipelines};
+use heroku_rs::framework::apiclient::HerokuApiClient;
+use heroku_rs::framework::endpoint::Method;
+
+use serenity::framework::standard::{macros::command, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::commands::heroku::{
+    bot_config, bot_deploy_queue, bot_store, current_release, heroku_client, release_commit,
+};
+use crate::deploy_locks::deploy_blocker;
+use crate::store::DeployLock;
+
+#[command]
+#[min_args(2)]
+#[description = "Stop anyone from deploying, rolling back, or updating the config of an app"]
+#[example = "~lock_deploys app_name_or_id reason"]
+#[example = "~lock_deploys my_app investigating elevated error rates"]
+pub fn lock_deploys(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let reason = args.rest().to_string();
+
+    let lock = DeployLock {
+        owner: msg.author.tag(),
+        reason: reason.clone(),
+        locked_at: Utc::now(),
+    };
+
+    let existing_lock = bot_store(ctx).update(|state| {
+        if let Some(existing_lock) = state.deploy_locks.get(&app_name) {
+            return Some(existing_lock.clone());
+        }
+
+        state.deploy_locks.insert(app_name.clone(), lock);
+        None
+    })?;
+
+    if let Some(existing_lock) = existing_lock {
+        msg.reply(
+            &ctx,
+            format!(
+                "Deploys to {} are already locked by {}: {}",
+                app_name, existing_lock.owner, existing_lock.reason
+            ),
+        )?;
+
+        return Ok(());
+    }
+
+    bot_store(ctx).record(&msg.author.tag(), &app_name, "lock_deploys", &reason)?;
+
+    msg.reply(
+        ctx,
+        format!("Deploys to {} are now locked: {}", app_name, reason),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[description = "Allow deploys to an app that was locked with lock_deploys again"]
+#[example = "~unlock_deploys app_name_or_id"]
+#[example = "~unlock_deploys my_app"]
+pub fn unlock_deploys(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let removed_lock = bot_store(ctx).update(|state| state.deploy_locks.remove(&app_name))?;
+
+    match removed_lock {
+        Some(lock) => {
+            bot_store(ctx).record(
+                &msg.author.tag(),
+                &app_name,
+                "unlock_deploys",
+                &format!("lock by {}: {}", lock.owner, lock.reason),
+            )?;
+
+            msg.reply(
+                ctx,
+                format!(
+                    "Deploys to {} are unlocked (they were locked by {}: {})",
+                    app_name, lock.owner, lock.reason
+                ),
+            )?;
+        }
+        None => {
+            msg.reply(ctx, format!("Deploys to {} are not locked", app_name))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[description = "Show the deploys that are running or waiting for an app"]
+#[example = "~deploy_queue app_name_or_id"]
+#[example = "~deploy_queue my_app"]
+pub fn deploy_queue(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let queued_deploys = bot_deploy_queue(ctx).list(&app_name);
+
+    if queued_deploys.is_empty() {
+        msg.reply(
+            ctx,
+            format!("No deploys are running or queued for {}", app_name),
+        )?;
+
+        return Ok(());
+    }
+
+    let mut response = format!("Deploys for {}\n", app_name);
+
+    for (position, deploy) in queued_deploys.iter().enumerate() {
+        let state = if position == 0 { "running" } else { "queued" };
+
+        response.push_str(&format!(
+            "\nID: {}\nState: {}\nRef: {}\nRequested By: {}\nRequested At: {}\n",
+            deploy.id,
+            state,
+            deploy.git_ref,
+            deploy.requested_by,
+            deploy.requested_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        ));
+    }
+
+    msg.reply(ctx, response)?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[description = "Remove a deploy that is waiting in an app's deploy queue"]
+#[example = "~cancel_deploy app_name_or_id deploy_id"]
+#[example = "~cancel_deploy my_app 3"]
+pub fn cancel_deploy(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let deploy_id = args
+        .single::<u64>()
+        .expect("You must include the id of the deploy to cancel");
+
+    match bot_deploy_queue(ctx).cancel(&app_name, deploy_id) {
+        Ok(deploy) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "Deploy {} of {} to {} (requested by {}) has been removed from the queue",
+                    deploy.id, deploy.git_ref, app_name, deploy.requested_by
+                ),
+            )?;
+        }
+        Err(reason) => {
+            msg.reply(ctx, reason)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[num_args(3)]
+#[description = "Promote the release running in one stage of a Heroku pipeline to the apps in another stage"]
+#[example = "~promote pipeline_name_or_id from_stage to_stage"]
+#[example = "~promote crates-io staging production"]
+pub fn promote(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let pipeline_name = args
+        .single::<String>()
+        .expect("You must include a pipeline name");
+
+    let from_stage = args
+        .single::<String>()
+        .expect("You must include the stage to promote from");
+
+    let to_stage = args
+        .single::<String>()
+        .expect("You must include the stage to promote to");
+
+    let pipeline = heroku_client(ctx).request(&pipelines::PipelineDetails {
+        pipeline_id: pipeline_name.clone(),
+    })?;
+
+    let couplings = heroku_client(ctx).request(&pipelines::PipelineCouplingByPipelineList {
+        pipeline_id: pipeline.id.clone(),
+    })?;
+
+    let source_ids: Vec<String> = couplings
+        .iter()
+        .filter(|coupling| coupling.stage == from_stage)
+        .map(|coupling| coupling.app.id.clone())
+        .collect();
+
+    let source_id = match source_ids.as_slice() {
+        [source_id] => source_id.clone(),
+        _ => {
+            msg.reply(
+                ctx,
+                format!(
+                    "Stage {} of pipeline {} must have exactly one app to promote from, it has {}",
+                    from_stage,
+                    pipeline.name,
+                    source_ids.len()
+                ),
+            )?;
+
+            return Ok(());
+        }
+    };
+
+    let mut targets: Vec<apps::App> = Vec::new();
+
+    for coupling in couplings
+        .iter()
+        .filter(|coupling| coupling.stage == to_stage)
+    {
+        targets.push(heroku_client(ctx).request(&apps::AppDetails {
+            app_id: coupling.app.id.clone(),
+        })?);
+    }
+
+    if targets.is_empty() {
+        msg.reply(
+            ctx,
+            format!(
+                "Stage {} of pipeline {} does not have any apps to promote to",
+                to_stage, pipeline.name
+            ),
+        )?;
+
+        return Ok(());
+    }
+
+    // Always queue for target apps in the same order, so two
+    // promotions with overlapping targets can't wait on each other
+    targets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for target in &targets {
+        if deploys_blocked(ctx, msg, &target.name)? {
+            return Ok(());
+        }
+    }
+
+    let source_app = heroku_client(ctx).request(&apps::AppDetails { app_id: source_id })?;
+
+    let release = current_release(ctx, &source_app.name)?;
+    let commit =
+        release_commit(ctx, &source_app.name, &release).unwrap_or_else(|| String::from("unknown"));
+
+    let target_names: Vec<String> = targets.iter().map(|app| app.name.clone()).collect();
+
+    run_in_background(ctx, msg, "promote", move |ctx, msg| {
+        // The tickets keep the target apps' deploy queues
+        // blocked until the promotion has finished
+        let mut tickets = Vec::new();
+
+        for target in &targets {
+            let ticket = bot_deploy_queue(ctx).enqueue(
+                &target.name,
+                &format!("promotion of {} v{}", source_app.name, release.version),
+                &msg.author.tag(),
+            );
+
+            if ticket.position > 0 {
+                msg.reply(
+                    ctx,
+                    format!(
+                        "Waiting for {} other deploy(s) of {} to finish",
+                        ticket.position, target.name
+                    ),
+                )?;
+            }
+
+            if !ticket.wait_for_turn() {
+                msg.reply(
+                    ctx,
+                    format!(
+                        "The promotion was cancelled in the deploy queue of {}",
+                        target.name
+                    ),
+                )?;
+
+                return Ok(());
+            }
+
+            tickets.push(ticket);
+        }
+
+        msg.reply(
+            ctx,
+            format!(
+                "Promoting {} release v{} (commit {}: {}) from {} to {}: {}",
+                source_app.name,
+                release.version,
+                commit,
+                release.description,
+                from_stage,
+                to_stage,
+                target_names.join(", ")
+            ),
+        )?;
+
+        let promotion = heroku_client(ctx).request(&pipelines::PipelinePromotionCreate {
+            params: pipelines::PipelinePromotionCreateParams {
+                pipeline: pipelines::post::PipelineParam {
+                    id: pipeline.id.clone(),
+                },
+                source: pipelines::post::SourceParam {
+                    app: pipelines::post::AppParam {
+                        id: source_app.id.clone(),
+                    },
+                },
+                targets: targets
+                    .iter()
+                    .map(|target| pipelines::post::TargetParam {
+                        app: pipelines::post::AppParam {
+                            id: target.id.clone(),
+                        },
+                    })
+                    .collect(),
+            },
+        })?;
+
+        bot_store(ctx).record(
+            &msg.author.tag(),
+            &pipeline.name,
+            "promote",
+            &format!(
+                "{} v{} (commit {}) from {} to {}",
+                source_app.name,
+                release.version,
+                commit,
+                from_stage,
+                target_names.join(", ")
+            ),
+        )?;
+
+        let (mut last_check, mut last_reply) = (Instant::now(), Instant::now());
+
+        let check_interval = Duration::from_secs(bot_config(ctx).build_check_interval);
+        let message_display_interval =
+            Duration::from_secs(bot_config(ctx).build_message_display_interval);
+
+        loop {
+            if last_check.elapsed() >= check_interval {
+                let promotion =
+                    heroku_client(ctx).request(&pipelines::PipelinePromotionDetails {
+                        promotion_id: promotion.id.clone(),
+                    })?;
+
+                if promotion.status != "pending" {
+                    break;
+                }
+
+                last_check = Instant::now();
+            }
+
+            if last_reply.elapsed() >= message_display_interval {
+                msg.channel_id.say(
+                    ctx,
+                    format!("Promotion {} is still pending...", &promotion.id),
+                )?;
+
+                last_reply = Instant::now();
+            }
+
+            std::thread::sleep(Duration::from_millis(500));
+        }
+
+        let promotion_targets =
+            heroku_client(ctx).request(&pipelines::PipelinePromotionTargetList {
+                promotion_id: promotion.id.clone(),
+            })?;
+
+        let mut response = format!(
+            "Promotion of {} release v{} to {} has finished\n",
+            source_app.name, release.version, to_stage
+        );
+
+        for promotion_target in promotion_targets {
+            let app_name = targets
+                .iter()
+                .find(|target| target.id == promotion_target.app.id)
+                .map(|target| target.name.clone())
+                .unwrap_or_else(|| promotion_target.app.id.clone());
+
+            response.push_str(&format!(
+                "\nApp: {}\nStatus: {}\n",
+                app_name, promotion_target.status
+            ));
+
+            if let Some(error_message) = promotion_target.error_message {
+                response.push_str(&format!("Error: {}\n", error_message));
+            }
+        }
+
+        msg.reply(ctx, response)?;
+
+        Ok(())
+    });
+
+    Ok(())
+}
+
+#[command]
+#[min_args(1)]
+#[max_args(2)]
+#[description = "Cancel a build that is in progress. Without a build id, cancels the build of the running deploy \
+(or the most recent pending build)"]
+#[example = "~cancel_build app_name_or_id [build_id]"]
+#[example = "~cancel_build my_app"]
+#[example = "~cancel_build my_app a30c6830-7e47-47ce-9f8d-1a883e4a9beb"]
+pub fn cancel_build(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let running_build = bot_deploy_queue(ctx)
+        .running(&app_name)
+        .and_then(|deploy| deploy.build_id);
+
+    let build_id = match args.single::<String>().ok().or(running_build) {
+        Some(build_id) => build_id,
+        None => {
+            let builds = heroku_client(ctx).request(&builds::BuildList {
+                app_id: app_name.clone(),
+            })?;
+
+            match builds
+                .into_iter()
+                .filter(|build| build.status == "pending")
+                .max_by(|a, b| a.created_at.cmp(&b.created_at))
+            {
+                Some(build) => build.id,
+                None => {
+                    msg.reply(ctx, format!("There are no pending builds for {}", app_name))?;
+
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    // heroku_rs does not have an endpoint for cancelling builds
+    heroku_client(ctx).request(&custom::CustomEndpointSimple::new(
+        format!("apps/{}/builds/{}", app_name, build_id),
+        Method::Delete,
+    ))?;
+
+    // Stops the deploy_app command that is monitoring the build, if there is one
+    bot_deploy_queue(ctx).cancel_build(&app_name, &build_id, &msg.author.tag());
+
+    bot_store(ctx).record(
+        &msg.author.tag(),
+        &app_name,
+        "cancel_build",
+        &format!("build {}", build_id),
+    )?;
+
+    msg.channel_id.say(
+        &ctx,
+        format!(
+            "Build {} for {} has been cancelled by {}",
+            build_id, app_name, msg.author
+        ),
+    )?;
+
+    Ok(())
+}
+
+// Lets people know when they change an app in the middle of a deploy
+pub fn warn_if_deploying(ctx: &Context, msg: &Message, app_name: &str) -> CommandResult {
+    if let Some(deploy) = bot_deploy_queue(ctx).running(app_name) {
+        msg.reply(
+            ctx,
+            format!(
+                "Warning: {} is being deployed right now (deploy {} of {} by {})",
+                app_name, deploy.id, deploy.git_ref, deploy.requested_by
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Replies with the reason and returns true when an app is
+// locked or a freeze window is active, so callers can bail out
+pub fn deploys_blocked(ctx: &Context, msg: &Message, app_name: &str) -> Result<bool, CommandError> {
+    let blocker = bot_store(ctx)
+        .read(|state| deploy_blocker(state, &bot_config(ctx).freeze_windows, app_name, Utc::now()));
+
+    match blocker {
+        Some(blocker) => {
+            msg.reply(ctx, blocker)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// Finishes a deploy on its own thread, so waiting in the deploy queue and
+// watching the build don't hold on to one of the framework's threads
+pub fn run_in_background<F>(ctx: &Context, msg: &Message, command_name: &'static str, deploy: F)
+where
+    F: FnOnce(&Context, &Message) -> CommandResult + Send + 'static,
+{
+    let ctx = ctx.clone();
+    let msg = msg.clone();
+
+    thread::spawn(move || {
+        if let Err(error) = deploy(&ctx, &msg) {
+            msg.reply(
+                &ctx,
+                format!(
+                    "There was an error when running {}: {:?}",
+                    command_name, error
+                ),
+            )
+            .ok();
+        }
+    });
+}
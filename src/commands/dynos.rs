@@ -0,0 +1,286 @@
+use chrono::Utc;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use heroku_rs::endpoints::dynos;
+use heroku_rs::framework::apiclient::HerokuApiClient;
+
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::deploys::{run_in_background, warn_if_deploying};
+use crate::commands::heroku::{
+    bot_config, bot_store, heroku_client, parse_heroku_time, reply_in_chunks,
+};
+
+use crate::utilities::*;
+
+#[command]
+#[num_args(1)]
+#[description = "List the dynos of an app with their type, size, state, uptime and release"]
+#[example = "~dynos app_name_or_id"]
+#[example = "~dynos my_app"]
+pub fn dynos(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let mut app_dynos = heroku_client(ctx).request(&dynos::DynoList {
+        app_id: app_name.clone(),
+    })?;
+
+    if app_dynos.is_empty() {
+        msg.reply(ctx, format!("{} does not have any dynos running", app_name))?;
+
+        return Ok(());
+    }
+
+    app_dynos.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut response = format!("Here are the dynos for {}\n", app_name);
+
+    for dyno in app_dynos {
+        response.push_str(&dyno_info_response(dyno));
+    }
+
+    reply_in_chunks(ctx, msg, &response)?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[description = "Restart a single dyno of an app"]
+#[example = "~restart_dyno app_name_or_id dyno_name"]
+#[example = "~restart_dyno my_app web.1"]
+pub fn restart_dyno(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let dyno_name = args
+        .single::<String>()
+        .expect("You must include a dyno name");
+
+    warn_if_deploying(ctx, msg, &app_name)?;
+
+    let _response = heroku_client(ctx).request(&dynos::DynoRestart {
+        app_id: app_name.clone(),
+        dyno_id: dyno_name.clone(),
+    })?;
+
+    bot_store(ctx).record(&msg.author.tag(), &app_name, "restart_dyno", &dyno_name)?;
+
+    msg.reply(
+        ctx,
+        format!("Dyno {} in {} has been restarted.", dyno_name, app_name),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+#[description = "Stop a single dyno of an app. Dynos that are part of a formation will be started again by Heroku"]
+#[example = "~stop_dyno app_name_or_id dyno_name"]
+#[example = "~stop_dyno my_app worker.2"]
+pub fn stop_dyno(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let dyno_name = args
+        .single::<String>()
+        .expect("You must include a dyno name");
+
+    warn_if_deploying(ctx, msg, &app_name)?;
+
+    let _response = heroku_client(ctx).request(&dynos::DynoActionStop {
+        app_id: app_name.clone(),
+        dyno_id: dyno_name.clone(),
+    })?;
+
+    bot_store(ctx).record(&msg.author.tag(), &app_name, "stop_dyno", &dyno_name)?;
+
+    msg.reply(
+        ctx,
+        format!("Dyno {} in {} has been stopped.", dyno_name, app_name),
+    )?;
+
+    Ok(())
+}
+
+const DEFAULT_ROLLING_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+// How long a restarted dyno gets to come back up before the rolling restart is aborted
+const DYNO_RESTART_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[command]
+#[min_args(1)]
+#[max_args(4)]
+#[description = "Restart the dynos of an app (or of one formation) one at a time, \
+waiting for each to come back up before moving on to the next"]
+#[example = "~rolling_restart app_name_or_id [formation] [--delay 30s]"]
+#[example = "~rolling_restart my_app"]
+#[example = "~rolling_restart my_app web --delay 1m"]
+pub fn rolling_restart(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut restart_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+
+    let delay = match take_flag_value(&mut restart_args, "--delay")? {
+        Some(delay) => parse_duration(&delay)?,
+        None => DEFAULT_ROLLING_RESTART_DELAY,
+    };
+
+    if restart_args.is_empty() || restart_args.len() > 2 {
+        msg.reply(
+            &ctx,
+            "You must include an app name (and optionally a formation and --delay)",
+        )?;
+
+        return Ok(());
+    }
+
+    let app_name = restart_args.remove(0);
+    let formation_name = restart_args.pop();
+
+    let mut app_dynos: Vec<dynos::Dyno> = heroku_client(ctx)
+        .request(&dynos::DynoList {
+            app_id: app_name.clone(),
+        })?
+        .into_iter()
+        // One-off dynos (run.*) aren't part of a formation and
+        // don't come back after a restart, so skip them by default
+        .filter(|dyno| match &formation_name {
+            Some(name) => &dyno.r#type == name,
+            None => dyno.r#type != "run",
+        })
+        .collect();
+
+    if app_dynos.is_empty() {
+        msg.reply(
+            ctx,
+            format!("There are no dynos to restart in {}", app_name),
+        )?;
+
+        return Ok(());
+    }
+
+    app_dynos.sort_by(|a, b| a.name.cmp(&b.name));
+
+    warn_if_deploying(ctx, msg, &app_name)?;
+
+    bot_store(ctx).record(
+        &msg.author.tag(),
+        &app_name,
+        "rolling_restart",
+        formation_name.as_deref().unwrap_or("all formations"),
+    )?;
+
+    run_in_background(ctx, msg, "rolling_restart", move |ctx, msg| {
+        let check_interval = Duration::from_secs(bot_config(ctx).build_check_interval);
+        let total = app_dynos.len();
+
+        for (index, dyno) in app_dynos.iter().enumerate() {
+            msg.channel_id.say(
+                ctx,
+                format!("Restarting {} ({}/{})...", dyno.name, index + 1, total),
+            )?;
+
+            let _response = heroku_client(ctx).request(&dynos::DynoRestart {
+                app_id: app_name.clone(),
+                dyno_id: dyno.name.clone(),
+            })?;
+
+            let restarted_at = Instant::now();
+
+            loop {
+                thread::sleep(check_interval);
+
+                // The dyno may briefly not exist while it is being replaced
+                let state = heroku_client(ctx)
+                    .request(&dynos::DynoDetails {
+                        app_id: app_name.clone(),
+                        dyno_id: dyno.name.clone(),
+                    })
+                    .ok()
+                    .map(|restarted| (restarted.state, restarted.updated_at));
+
+                match state {
+                    Some((state, updated_at)) if state == "up" && updated_at != dyno.updated_at => {
+                        break
+                    }
+                    Some((state, updated_at))
+                        if state == "crashed" && updated_at != dyno.updated_at =>
+                    {
+                        msg.reply(
+                            ctx,
+                            format!(
+                                "{} crashed after restarting, aborting the rolling restart of {} \
+                                ({} of {} dynos restarted)",
+                                dyno.name, app_name, index, total
+                            ),
+                        )?;
+
+                        return Ok(());
+                    }
+                    _ if restarted_at.elapsed() >= DYNO_RESTART_TIMEOUT => {
+                        msg.reply(
+                            ctx,
+                            format!(
+                                "{} did not come back up within {} minutes, aborting the rolling restart of {} \
+                                ({} of {} dynos restarted)",
+                                dyno.name,
+                                DYNO_RESTART_TIMEOUT.as_secs() / 60,
+                                app_name,
+                                index,
+                                total
+                            ),
+                        )?;
+
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+
+            msg.channel_id.say(
+                ctx,
+                format!("{} is back up ({}/{})", dyno.name, index + 1, total),
+            )?;
+
+            if index + 1 < total {
+                thread::sleep(delay);
+            }
+        }
+
+        msg.reply(
+            ctx,
+            format!(
+                "All {} dynos in {} have been restarted one at a time",
+                total, app_name
+            ),
+        )?;
+
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn dyno_info_response(dyno: heroku_rs::endpoints::dynos::Dyno) -> String {
+    // Heroku updates a dyno whenever its state changes,
+    // so for a running dyno that is when it came up
+    let uptime = match parse_heroku_time(&dyno.updated_at) {
+        Some(updated_at) if dyno.state == "up" => {
+            format!("started {}", relative_time(updated_at, Utc::now()))
+        }
+        _ => String::from("not running"),
+    };
+
+    format!(
+        "\nName: {}\nType: {}\nSize: {}\nState: {}\nUptime: {}\nRelease: v{}\n",
+        dyno.name, dyno.r#type, dyno.size, dyno.state, uptime, dyno.release.version,
+    )
+}
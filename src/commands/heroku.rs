@@ -1,66 +1,64 @@
-use crate::HerokuClientKey;
-use heroku_rs::endpoints::{apps, builds, config_vars, dynos, formations, releases};
+use crate::{HerokuClientKey, ReleasesClientKey};
+use heroku_rs::endpoints::{apps, builds, config_vars, dynos, formations, releases, slugs};
 use heroku_rs::framework::apiclient::HerokuApiClient;
+use heroku_rs::framework::HttpApiClient;
 
-use serde::Deserialize;
+use ipnet::IpNet;
+
+use chrono::{DateTime, Utc};
 
 use serenity::framework::standard::{macros::command, Args, CommandResult};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::error::Error;
 
 use std::time::{Duration, Instant};
 
+use crate::commands::deploys::{deploys_blocked, run_in_background, warn_if_deploying};
+use crate::commands::logs::fetch_logs;
+
 use crate::config::Config;
 
-use crate::utilities::*;
+use crate::config_vars::{
+    update_config_var, ConfigVarClient, ConfigVarLocks, ConfigVarWrite, MAX_CONFIG_VARS_SIZE,
+};
 
-use reqwest::blocking::Client as reqwest_client;
-use reqwest::header::{self, HeaderMap, HeaderValue};
+use crate::costs::{cost_change, format_cost, formation_cost, total_cost};
 
-#[derive(Debug, Deserialize)]
-struct HerokuApp {
-    id: String,
-    name: String,
-    released_at: String,
-    web_url: String,
-}
+use crate::deploy_queue::DeployQueue;
 
-#[derive(Debug, Deserialize)]
-struct GitHubResponse {
-    sha: String,
-}
+use crate::github::{
+    commit_url, compare_url, parse_deploy_ref, pull_request_from_commit, pull_request_url, CiState,
+    DeployRef, GitHubClient,
+};
 
-#[derive(Debug)]
-struct GitHubClient {
-    client: reqwest_client,
-    headers: HeaderMap,
-}
+use crate::ip_blocks::{
+    self, block_overlaps, blocked_ips_lines, blocking_entry, canonical_ip_block, matching_entries,
+    parse_blocked_entry, parse_bulk_blocks, parse_ip_block, MAX_IMPORT_SIZE,
+};
 
-impl GitHubClient {
-    pub fn new(auth_token: String) -> Self {
-        let github_client = reqwest_client::new();
+use crate::log_drain::{
+    parse_log_session_line, rank_ips, DrainStats, RouterHit, TRAFFIC_RETENTION,
+};
 
-        let mut headers = HeaderMap::new();
-        let accept = HeaderValue::from_str("application/vnd.github.v3+json");
-        headers.insert(header::ACCEPT, accept.unwrap());
+use crate::logs::MAX_LOG_LINES;
 
-        let auth = HeaderValue::from_str(&format!("token {}", auth_token));
-        headers.insert(header::AUTHORIZATION, auth.unwrap());
+use crate::release_diff::{addon_change, changed_config_vars, AddonChange};
 
-        // Required for the GitHub API
-        // https://developer.github.com/v3/#user-agent-required
-        let useragent = HeaderValue::from_str("rust-lang/crates-io-ops-bot");
-        headers.insert(header::USER_AGENT, useragent.unwrap());
+use crate::release_targets::resolve_release_target;
 
-        GitHubClient {
-            client: github_client,
-            headers,
-        }
-    }
-}
+use crate::scaling::{
+    check_scaling_limits, formation_batch_update, formation_changes_table, needs_confirmation,
+    parse_scale_changes, FormationBatchEntry, FormationChange,
+};
+
+use crate::store::{BlockedIp, Store};
+
+use crate::utilities::*;
 
 // Get app by name or id
 #[command]
@@ -80,7 +78,10 @@ pub fn get_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResul
 
     let formations = heroku_client(ctx).request(&formations::FormationList { app_id: app_name })?;
 
-    msg.reply(&ctx, app_formations_response(formations))?;
+    msg.reply(
+        &ctx,
+        app_formations_response(formations, &bot_config(ctx).dyno_prices),
+    )?;
 
     Ok(())
 }
@@ -109,6 +110,10 @@ pub fn update_app_config(ctx: &mut Context, msg: &Message, mut args: Args) -> Co
         .single::<String>()
         .expect("You must include a config variable value");
 
+    if deploys_blocked(ctx, msg, &app_name)? {
+        return Ok(());
+    }
+
     if AUTHORIZED_CONFIG_VARS.contains(&config_var_key_reference) {
         let mut config_var = HashMap::new();
         config_var.insert(config_var_key, config_var_value);
@@ -135,30 +140,79 @@ pub fn update_app_config(ctx: &mut Context, msg: &Message, mut args: Args) -> Co
 const BLOCKED_IPS_ENV_VAR: &str = "BLOCKED_IPS";
 
 #[command]
-#[num_args(2)]
-#[description = "Block an IP address"]
-#[example = "~block_ip app_name_or_id ip_address_to_block"]
+#[min_args(2)]
+#[description = "Block an IP address or CIDR range (IPv4 or IPv6), optionally with a reason. \
+Use --for to unblock it automatically after a while"]
+#[example = "~block_ip app_name_or_id ip_address_or_range_to_block [reason] [--for 24h]"]
 #[example = "~block_ip my_app 123.4.5.67"]
+#[example = "~block_ip my_app 2001:db8::/32 scraping every crate page --for 24h"]
 pub fn block_ip(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    let app_name = args
-        .single::<String>()
-        .expect("You must include an app name");
+    let mut block_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
 
-    let ip_addr = args
-        .single::<String>()
-        .expect("You must include an IP address to block");
+    let expires_in = match take_flag_value(&mut block_args, "--for")? {
+        Some(duration) => Some(parse_duration(&duration)?),
+        None => None,
+    };
+
+    if block_args.len() < 2 {
+        msg.reply(
+            &ctx,
+            "You must include an app name and an IP address to block \
+            (and optionally a reason and --for)",
+        )?;
+
+        return Ok(());
+    }
 
-    let current_config_vars =
-        heroku_client(ctx).request(&config_vars::AppConfigVarDetails { app_id: &app_name })?;
+    let app_name = block_args.remove(0);
+    let ip_input = block_args.remove(0);
 
-    // If the BLOCKED_IPS environmental variable does not
-    // currently exist, create it
-    if !blocked_ips_exist(&current_config_vars) {
-        let _response = heroku_client(&ctx).request(&config_vars::AppConfigVarUpdate {
-            app_id: &app_name,
-            params: empty_config_var(),
-        })?;
+    let reason = if block_args.is_empty() {
+        None
+    } else {
+        Some(block_args.join(" "))
+    };
+
+    let block = parse_ip_block(&ip_input)?;
+    let ip_addr = canonical_ip_block(&block);
+
+    let blocked_at = Utc::now();
+    let expires_at = match expires_in {
+        Some(expires_in) => {
+            match chrono::Duration::from_std(expires_in)
+                .ok()
+                .and_then(|expires_in| blocked_at.checked_add_signed(expires_in))
+            {
+                Some(expires_at) => Some(expires_at),
+                None => {
+                    msg.reply(&ctx, "The --for duration is too long")?;
+
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
+
+    let (created, overlaps) = update_blocked_ips(ctx, &app_name, |current| {
+        let mut blocked_ips_set = blocked_ips_set(current);
+
+        if !matching_entries(&block, &blocked_ips_set).is_empty() {
+            return (ConfigVarWrite::Unchanged, (false, None));
+        }
+
+        let overlaps = block_overlaps(&block, &blocked_ips_set);
+        blocked_ips_set.insert(ip_addr.clone());
+
+        (
+            ConfigVarWrite::Set(blocked_ips_value(blocked_ips_set)),
+            (current.is_none(), Some(overlaps)),
+        )
+    })?;
 
+    // If the BLOCKED_IPS environmental variable did not
+    // exist yet, blocking the IP address created it
+    if created {
         msg.reply(
             &ctx,
             format!(
@@ -168,326 +222,1480 @@ pub fn block_ip(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResu
         )?;
     }
 
-    let mut blocked_ips_set = current_blocked_ip_addresses(current_config_vars);
+    if let Some((covered_by, covers)) = overlaps {
+        bot_store(ctx).update(|state| {
+            state
+                .blocked_ips
+                .entry(app_name.clone())
+                .or_default()
+                .insert(
+                    ip_addr.clone(),
+                    BlockedIp {
+                        blocked_by: msg.author.tag(),
+                        blocked_at,
+                        expires_at,
+                        channel_id: Some(msg.channel_id.0),
+                        reason: reason.clone(),
+                    },
+                );
+        })?;
 
-    if blocked_ips_set.contains(&ip_addr) {
-        msg.reply(
-            &ctx,
-            format!("{} is already blocked for {}", &ip_addr, app_name),
-        )?;
-    } else {
-        blocked_ips_set.insert(ip_addr.clone());
+        let mut details = ip_addr.clone();
+
+        if let Some(reason) = &reason {
+            details.push_str(&format!(": {}", reason));
+        }
 
-        let updated_config_var = blocked_ips_config_var(blocked_ips_set);
+        if let Some(expires_at) = expires_at {
+            details.push_str(&format!(
+                " (until {})",
+                expires_at.format("%Y-%m-%d %H:%M UTC")
+            ));
+        }
 
-        let _response = heroku_client(ctx).request(&config_vars::AppConfigVarUpdate {
-            app_id: &app_name,
-            params: updated_config_var,
+        bot_store(ctx).record(&msg.author.tag(), &app_name, "block_ip", &details)?;
+
+        let mut response = format!("IP address {} has been blocked", ip_addr);
+
+        if let Some(expires_at) = expires_at {
+            response.push_str(&format!(
+                " until {}",
+                expires_at.format("%Y-%m-%d %H:%M UTC")
+            ));
+        }
+
+        if !covered_by.is_empty() {
+            response.push_str(&format!(
+                "\nWarning: {} was already covered by {}",
+                ip_addr,
+                covered_by.join(", ")
+            ));
+        }
+
+        if !covers.is_empty() {
+            response.push_str(&format!(
+                "\nWarning: {} also covers the existing entries {}",
+                ip_addr,
+                covers.join(", ")
+            ));
+        }
+
+        msg.reply(ctx, response)?;
+    } else {
+        // Blocking an address again replaces when its block expires,
+        // so --for can extend a block and leaving it out makes it permanent
+        let expiry_changed = bot_store(ctx).update(|state| {
+            let blocked_ip = state
+                .blocked_ips
+                .get_mut(&app_name)
+                .and_then(|blocked_ips| blocked_ips.get_mut(&ip_addr));
+
+            match blocked_ip {
+                Some(blocked_ip) => {
+                    let changed = blocked_ip.expires_at != expires_at;
+                    blocked_ip.expires_at = expires_at;
+                    changed
+                }
+                // Blocked outside the bot, which only needs to
+                // remember it when there is an expiry to enforce
+                None if expires_at.is_some() => {
+                    state
+                        .blocked_ips
+                        .entry(app_name.clone())
+                        .or_default()
+                        .insert(
+                            ip_addr.clone(),
+                            BlockedIp {
+                                blocked_by: msg.author.tag(),
+                                blocked_at,
+                                expires_at,
+                                channel_id: Some(msg.channel_id.0),
+                                reason: reason.clone(),
+                            },
+                        );
+                    true
+                }
+                None => false,
+            }
         })?;
 
-        msg.reply(ctx, format!("IP address {} has been blocked", ip_addr))?;
-    };
+        let mut response = format!("{} is already blocked for {}", &ip_addr, app_name);
+
+        if expiry_changed {
+            match expires_at {
+                Some(expires_at) => response.push_str(&format!(
+                    ", the block now expires at {}",
+                    expires_at.format("%Y-%m-%d %H:%M UTC")
+                )),
+                None => response.push_str(", the block no longer expires"),
+            }
+        }
+
+        msg.reply(&ctx, response)?;
+    }
 
     Ok(())
 }
 
 #[command]
 #[num_args(2)]
-#[description = "Unblock an IP address"]
-#[example = "~unblock_ip app_name_or_id ip_address_to_unblock"]
+#[description = "Unblock an IP address or CIDR range"]
+#[example = "~unblock_ip app_name_or_id ip_address_or_range_to_unblock"]
 #[example = "~unblock_ip my_app 123.4.5.6"]
 pub fn unblock_ip(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     let app_name = args
         .single::<String>()
         .expect("You must include an app name");
 
-    let ip_addr = args
+    let ip_input = args
         .single::<String>()
         .expect("You must include an IP address to unblock");
 
-    let current_config_vars =
-        heroku_client(ctx).request(&config_vars::AppConfigVarDetails { app_id: &app_name })?;
-
-    if !blocked_ips_exist(&current_config_vars) {
-        msg.reply(
-            &ctx,
-            format!("No IP addresses are currently blocked for {}", &app_name),
-        )?;
-
-        return Ok(());
-    }
-
-    let mut blocked_ips_set = current_blocked_ip_addresses(current_config_vars);
-
-    if !blocked_ips_set.contains(&ip_addr) {
-        msg.reply(
-            &ctx,
-            format!("{} is not currently blocked for {}", &ip_addr, app_name),
-        )?;
-    } else {
-        blocked_ips_set.remove(&ip_addr);
-
-        // Removes config variable from the Heroku application
-        // if there are no more blocked ip addresses
-        if blocked_ips_set.is_empty() {
-            let _response = heroku_client(ctx).request(&config_vars::AppConfigVarDelete {
-                app_id: &app_name,
-                params: null_blocked_ips_config_var(),
+    // Entries added outside the bot may have host bits set, and
+    // should still be unblocked the way !blocked_ips shows them
+    let block = parse_blocked_entry(&ip_input)?;
+    let ip_addr = canonical_ip_block(&block);
+
+    let response = match remove_ip_block(
+        &*heroku_client(ctx),
+        &bot_config_var_locks(ctx),
+        &app_name,
+        &block,
+    )? {
+        IpUnblock::NothingBlocked => {
+            format!("No IP addresses are currently blocked for {}", &app_name)
+        }
+        IpUnblock::NotBlocked(Some(range)) => format!(
+            "{} is not blocked on its own for {}, but it is covered by {}",
+            &ip_addr, app_name, range
+        ),
+        IpUnblock::NotBlocked(None) => {
+            format!("{} is not currently blocked for {}", &ip_addr, app_name)
+        }
+        IpUnblock::Removed { entries, none_left } => {
+            bot_store(ctx).update(|state| {
+                for entry in &entries {
+                    state.forget_blocked_ip(&app_name, entry);
+                }
             })?;
 
-            msg.reply(
-                ctx,
+            bot_store(ctx).record(&msg.author.tag(), &app_name, "unblock_ip", &ip_addr)?;
+
+            if none_left {
                 format!(
                     "IP address {} has been unblocked, there are now no unblocked IP addresses",
                     ip_addr,
-                ),
-            )?;
-        } else {
-            let _response = heroku_client(ctx).request(&config_vars::AppConfigVarUpdate {
-                app_id: &app_name,
-                params: blocked_ips_config_var(blocked_ips_set),
-            })?;
+                )
+            } else {
+                format!("IP address {} has been unblocked", ip_addr)
+            }
+        }
+    };
 
-            msg.reply(ctx, format!("IP address {} has been unblocked", ip_addr))?;
-        };
-    }
+    msg.reply(ctx, response)?;
 
     Ok(())
 }
 
 #[command]
-#[num_args(4)]
-#[description = "Scales a formation of dynos within a Heroku application"]
-#[example = "~scale_app your_app_name_or_id name_of_formation number_of_dynos_to_scale_to size_of_dyno"]
-#[example = "~scale_app my_app web 3 standard-1X"]
-pub fn scale_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+#[num_args(1)]
+#[description = "List the IP addresses and ranges blocked for an app, with who blocked them, \
+when, why and when they expire"]
+#[example = "~blocked_ips app_name_or_id"]
+#[example = "~blocked_ips my_app"]
+pub fn blocked_ips(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     let app_name = args
         .single::<String>()
         .expect("You must include an app name");
 
-    let formation_name = args
-        .single::<String>()
-        .expect("You must include a formation name");
-
-    let quantity = args.single::<i32>().expect("You must include a quantity");
+    let blocked_ips_set = current_blocked_ips(ctx, &app_name)?;
 
-    let size = args.single::<String>().expect("You must include a size");
+    if blocked_ips_set.is_empty() {
+        msg.reply(
+            &ctx,
+            format!("No IP addresses are currently blocked for {}", &app_name),
+        )?;
 
-    let formation = heroku_client(ctx).request(&formations::FormationUpdate {
-        app_id: app_name.clone(),
-        formation_id: formation_name,
-        params: formations::FormationUpdateParams {
-            quantity: Some(quantity),
-            size: Some(size),
-        },
-    })?;
+        return Ok(());
+    }
 
-    msg.reply(ctx, formation_updated_response(app_name, formation))?;
+    let lines = bot_store(ctx).read(|state| {
+        blocked_ips_lines(
+            &blocked_ips_set,
+            state.blocked_ips.get(&app_name),
+            Utc::now(),
+        )
+    });
 
-    Ok(())
+    reply_in_chunks(
+        ctx,
+        msg,
+        &format!(
+            "{} IP addresses and ranges are blocked for {}\n{}",
+            lines.len(),
+            app_name,
+            lines.join("\n")
+        ),
+    )
 }
 
-// Get app by name or id
+// How many rejected entries block_ips lists in its reply
+const MAX_LISTED_REJECTIONS: usize = 10;
+
 #[command]
-#[num_args(1)]
-#[description = "Get a list of releases for a Heroku app"]
-#[example = "~get_app_releases app_name_or_id"]
-pub fn get_app_releases(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+#[min_args(1)]
+#[description = "Block every IP address and CIDR range in an attached text or CSV file \
+(one per line, in the first column), optionally with a reason"]
+#[example = "~block_ips app_name_or_id [reason]"]
+#[example = "~block_ips my_app abuse wave from the weekend"]
+pub fn block_ips(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     let app_name = args
         .single::<String>()
         .expect("You must include an app name");
 
-    let releases = heroku_client(ctx).request(&releases::ReleaseList { app_id: app_name })?;
+    let reason = Some(args.rest().trim().to_string()).filter(|reason| !reason.is_empty());
 
-    msg.reply(ctx, releases_response(releases))?;
+    let attachment = match msg.attachments.first() {
+        Some(attachment) => attachment,
+        None => {
+            msg.reply(
+                &ctx,
+                "You must attach a text or CSV file of IP addresses and ranges to block",
+            )?;
 
-    Ok(())
-}
+            return Ok(());
+        }
+    };
 
-#[command]
-#[num_args(2)]
-#[description = "Rollback an app to the code associated with a previous release"]
-#[example = "~rollback_app app_name version-to-rollback-to"]
-#[example = "~rollback_app my_app v5"]
-#[example = "~rollback_app my_app 5"]
-pub fn rollback_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    let app_name = args
-        .single::<String>()
-        .expect("You must include an app name");
+    if attachment.size > MAX_IMPORT_SIZE {
+        msg.reply(
+            &ctx,
+            format!(
+                "{} is {} KB, the most block_ips can import is {} KB",
+                attachment.filename,
+                attachment.size / 1024,
+                MAX_IMPORT_SIZE / 1024
+            ),
+        )?;
 
-    let version_to_rollback_to = args
-        .single::<String>()
-        .expect("You must include the version to roll back to");
+        return Ok(());
+    }
 
-    let _response = heroku_client(ctx).request(&releases::ReleaseRollback {
-        app_id: app_name.clone(),
-        params: releases::ReleaseRollbackParams {
-            release: version_to_rollback_to.clone(),
-        },
-    })?;
+    let text = String::from_utf8(attachment.download()?)
+        .map_err(|_| format!("{} is not a text or CSV file", attachment.filename))?;
 
-    msg.reply(
-        ctx,
-        format!(
-            "App {} was successfully rolled back to the code at {}",
-            app_name, version_to_rollback_to
-        ),
-    )?;
+    let bulk_blocks = update_blocked_ips(ctx, &app_name, |current| {
+        let mut blocked_ips_set = blocked_ips_set(current);
+        let bulk_blocks = parse_bulk_blocks(&text, &blocked_ips_set);
 
-    Ok(())
-}
+        if bulk_blocks.accepted.is_empty() {
+            return (ConfigVarWrite::Unchanged, Ok(bulk_blocks));
+        }
 
-#[command]
-#[description = "Get all apps associated with your Heroku account"]
-#[example = "~get_apps"]
-pub fn get_apps(ctx: &mut Context, msg: &Message, _args: Args) -> CommandResult {
-    let apps = heroku_client(ctx).request(&apps::AppList {})?;
+        // Creates BLOCKED_IPS if it doesn't exist yet
+        blocked_ips_set.extend(bulk_blocks.accepted.iter().cloned());
+        let value = blocked_ips_value(blocked_ips_set);
+
+        if value.len() > MAX_CONFIG_VARS_SIZE {
+            return (
+                ConfigVarWrite::Unchanged,
+                Err(format!(
+                    "Adding {} entries would make {} {} KB, over Heroku's {} KB limit for config vars. \
+                    Nothing was blocked",
+                    bulk_blocks.accepted.len(),
+                    BLOCKED_IPS_ENV_VAR,
+                    value.len().div_ceil(1024),
+                    MAX_CONFIG_VARS_SIZE / 1024
+                )),
+            );
+        }
 
-    msg.reply(ctx, apps_response(apps))?;
+        (ConfigVarWrite::Set(value), Ok(bulk_blocks))
+    })??;
+
+    if !bulk_blocks.accepted.is_empty() {
+        let blocked_at = Utc::now();
+
+        bot_store(ctx).update(|state| {
+            let blocked_ips = state.blocked_ips.entry(app_name.clone()).or_default();
+
+            for ip_addr in &bulk_blocks.accepted {
+                blocked_ips.insert(
+                    ip_addr.clone(),
+                    BlockedIp {
+                        blocked_by: msg.author.tag(),
+                        blocked_at,
+                        expires_at: None,
+                        channel_id: Some(msg.channel_id.0),
+                        reason: reason.clone(),
+                    },
+                );
+            }
+        })?;
+
+        bot_store(ctx).record(
+            &msg.author.tag(),
+            &app_name,
+            "block_ips",
+            &format!(
+                "{} entries from {}{}",
+                bulk_blocks.accepted.len(),
+                attachment.filename,
+                reason
+                    .as_ref()
+                    .map(|reason| format!(": {}", reason))
+                    .unwrap_or_default()
+            ),
+        )?;
+    }
+
+    let mut response = format!(
+        "Blocked {} new IP addresses and ranges for {} ({} rejected, {} duplicates)",
+        bulk_blocks.accepted.len(),
+        app_name,
+        bulk_blocks.rejected.len(),
+        bulk_blocks.duplicates.len()
+    );
+
+    if !bulk_blocks.rejected.is_empty() {
+        let listed: Vec<String> = bulk_blocks
+            .rejected
+            .iter()
+            .take(MAX_LISTED_REJECTIONS)
+            .map(|entry| format!("`{}`", entry))
+            .collect();
+
+        response.push_str(&format!("\nRejected: {}", listed.join(", ")));
+
+        if bulk_blocks.rejected.len() > MAX_LISTED_REJECTIONS {
+            response.push_str(&format!(
+                " and {} more",
+                bulk_blocks.rejected.len() - MAX_LISTED_REJECTIONS
+            ));
+        }
+    }
+
+    for (ip_addr, covered_by) in bulk_blocks.covered.iter().take(MAX_LISTED_REJECTIONS) {
+        response.push_str(&format!(
+            "\nWarning: {} was already covered by {}",
+            ip_addr,
+            covered_by.join(", ")
+        ));
+    }
+
+    for (ip_addr, covers) in bulk_blocks.covering.iter().take(MAX_LISTED_REJECTIONS) {
+        response.push_str(&format!(
+            "\nWarning: {} also covers the existing entries {}",
+            ip_addr,
+            covers.join(", ")
+        ));
+    }
+
+    let unlisted = bulk_blocks
+        .covered
+        .len()
+        .saturating_sub(MAX_LISTED_REJECTIONS)
+        + bulk_blocks
+            .covering
+            .len()
+            .saturating_sub(MAX_LISTED_REJECTIONS);
+
+    if unlisted > 0 {
+        response.push_str(&format!("\nand {} more overlap warnings", unlisted));
+    }
+
+    msg.reply(ctx, response)?;
 
     Ok(())
 }
 
 #[command]
 #[num_args(1)]
-#[description = "Restart all dynos associated with an app"]
-#[example = "~restart_app app_name_or_id"]
-pub fn restart_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+#[description = "Export the IP addresses and ranges blocked for an app as a text file"]
+#[example = "~export_blocked_ips app_name_or_id"]
+#[example = "~export_blocked_ips my_app"]
+pub fn export_blocked_ips(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     let app_name = args
         .single::<String>()
         .expect("You must include an app name");
 
-    let _response = heroku_client(ctx).request(&dynos::DynoAllRestart {
-        app_id: app_name.clone(),
-    })?;
+    let blocked_ips_set = current_blocked_ips(ctx, &app_name)?;
 
-    msg.reply(
-        ctx,
-        format!("All dynos in {} have been restarted.", app_name),
+    if blocked_ips_set.is_empty() {
+        msg.reply(
+            &ctx,
+            format!("No IP addresses are currently blocked for {}", &app_name),
+        )?;
+
+        return Ok(());
+    }
+
+    let export = ip_blocks::export_blocked_ips(&blocked_ips_set);
+    let file_name = format!("{}-blocked-ips.txt", app_name);
+
+    msg.channel_id.send_files(
+        &ctx.http,
+        vec![(export.as_bytes(), file_name.as_str())],
+        |m| {
+            m.content(format!(
+                "{}: Here are the {} IP addresses and ranges blocked for {}",
+                msg.author.mention(),
+                blocked_ips_set.len(),
+                app_name
+            ))
+        },
     )?;
 
     Ok(())
 }
 
-#[command]
-#[num_args(2)]
-#[description = "Deploy an app from a github reference (branch name, partial sha, or full sha)"]
-#[example = "~deploy_app app_name_or_id branch_commit_id_or_sha"]
-#[example = "~deploy_app my_app master"]
-pub fn deploy_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    let app_name = args
-        .single::<String>()
-        .expect("You must include an app name");
+pub enum IpUnblock {
+    // The app has no BLOCKED_IPS config var
+    NothingBlocked,
+    // The block isn't an entry of its own, but may be covered by a range
+    NotBlocked(Option<String>),
+    // The entries that were removed and whether that left none blocked
+    Removed {
+        entries: Vec<String>,
+        none_left: bool,
+    },
+}
 
-    let git_ref = args
-        .single::<String>()
-        .expect("You must include a git ref to deploy");
+// Removes a block from an app's BLOCKED_IPS, deleting the
+// config var once there are no more blocked IP addresses
+pub fn remove_ip_block(
+    heroku: &dyn ConfigVarClient,
+    locks: &ConfigVarLocks,
+    app_name: &str,
+    block: &IpNet,
+) -> Result<IpUnblock, Box<dyn Error>> {
+    update_config_var(heroku, locks, app_name, BLOCKED_IPS_ENV_VAR, |current| {
+        if current.is_none() {
+            return (ConfigVarWrite::Unchanged, IpUnblock::NothingBlocked);
+        }
 
-    let new_github_client = GitHubClient::new(bot_config(ctx).github_token.to_string());
+        let mut blocked_ips_set = blocked_ips_set(current);
+        let matching = matching_entries(block, &blocked_ips_set);
 
-    let github_request = new_github_client
-        .client
-        .get(&commit_info_url(ctx, git_ref))
-        .headers(new_github_client.headers);
-    let github_response = github_request
-        .send()
-        .and_then(|res| res.error_for_status())?;
-    let response_text = github_response.text().unwrap();
+        if matching.is_empty() {
+            let covered_by = blocking_entry(&canonical_ip_block(block), &blocked_ips_set);
+            return (ConfigVarWrite::Unchanged, IpUnblock::NotBlocked(covered_by));
+        }
 
-    let github_json: GitHubResponse = serde_json::from_str(&response_text).unwrap();
-    let git_sha = github_json.sha;
+        for entry in &matching {
+            blocked_ips_set.remove(entry);
+        }
 
-    let build = heroku_client(ctx).request(&builds::BuildCreate {
-        app_id: app_name.clone(),
-        params: builds::BuildCreateParams {
-            buildpacks: None,
-            source_blob: builds::SourceBlobParam {
-                checksum: None,
-                url: source_url(&ctx, &git_sha),
-                version: Some(git_sha.to_string()),
-            },
-        },
-    })?;
+        let none_left = blocked_ips_set.is_empty();
 
-    msg.reply(&ctx, build_response(&app_name, &build))?;
+        let write = if none_left {
+            ConfigVarWrite::Delete
+        } else {
+            ConfigVarWrite::Set(blocked_ips_value(blocked_ips_set))
+        };
 
-    let (mut last_check, mut last_reply) = (Instant::now(), Instant::now());
+        (
+            write,
+            IpUnblock::Removed {
+                entries: matching,
+                none_left,
+            },
+        )
+    })
+}
 
-    let build_check_interval = Duration::from_secs(bot_config(&ctx).build_check_interval);
-    let build_message_display_interval =
-        Duration::from_secs(bot_config(&ctx).build_message_display_interval);
+impl ConfigVarClient for HttpApiClient {
+    fn config_var(&self, app_name: &str, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let current_config_vars =
+            self.request(&config_vars::AppConfigVarDetails { app_id: app_name })?;
 
-    loop {
-        if last_check.elapsed() >= build_check_interval {
-            println!("checking build");
-            let build = heroku_client(ctx).request(&builds::BuildDetails {
-                app_id: app_name.clone(),
-                build_id: build.clone().id,
-            })?;
+        Ok(current_config_vars.get(key).cloned().flatten())
+    }
 
-            if build.status != "pending" {
-                break;
-            }
+    fn set_config_var(&self, app_name: &str, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let mut params = HashMap::new();
+        params.insert(key.to_string(), value.to_string());
 
-            last_check = Instant::now();
-        }
+        let _response = self.request(&config_vars::AppConfigVarUpdate {
+            app_id: app_name,
+            params,
+        })?;
+
+        Ok(())
+    }
 
-        if last_reply.elapsed() >= build_message_display_interval {
-            println!("making a reply in the channel");
-            msg.channel_id
-                .say(&ctx, format!("Build {} is still pending...", &build.id))?;
+    fn delete_config_var(&self, app_name: &str, key: &str) -> Result<(), Box<dyn Error>> {
+        let mut params = HashMap::new();
+        params.insert(key.to_string(), None);
 
-            last_reply = Instant::now();
-        }
+        let _response = self.request(&config_vars::AppConfigVarDelete {
+            app_id: app_name,
+            params,
+        })?;
 
-        std::thread::sleep(Duration::from_millis(500));
+        Ok(())
     }
+}
 
-    // Release the new build
-    let final_build_info_response = heroku_client(ctx).request(&builds::BuildDetails {
-        app_id: app_name.clone(),
-        build_id: build.clone().id,
-    });
+const DEFAULT_TOP_IPS_WINDOW: Duration = Duration::from_secs(10 * 60);
+const TOP_IPS_LIMIT: usize = 10;
 
-    if final_build_info_response.is_err() {
+#[command]
+#[min_args(1)]
+#[max_args(3)]
+#[description = "Rank the client IPs of an app by request count and error rate, \
+using the router logs from the last 10 minutes (or --window)"]
+#[example = "~top_ips app_name_or_id [--window 10m]"]
+#[example = "~top_ips my_app"]
+#[example = "~top_ips my_app --window 30m"]
+pub fn top_ips(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut top_ips_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+
+    let window = match take_flag_value(&mut top_ips_args, "--window")? {
+        Some(window) => parse_duration(&window)?,
+        None => DEFAULT_TOP_IPS_WINDOW,
+    };
+
+    if top_ips_args.len() != 1 {
         msg.reply(
             &ctx,
-            format!(
-                "Unable to get the final information for build {} for {}, cancelling release",
-                &build.id, &app_name
-            ),
+            "You must include an app name (and optionally --window)",
         )?;
 
         return Ok(());
     }
 
-    let final_build_info = final_build_info_response.unwrap();
+    let app_name = top_ips_args.remove(0);
+    let now = Utc::now();
+    let since = now
+        .checked_sub_signed(chrono::Duration::from_std(window)?)
+        .ok_or_else(|| format!("Invalid --window: {} is too long", format_duration(window)))?;
+
+    let mut hits = bot_drain_stats(ctx).traffic_since(&app_name, since);
+    let mut source = "the log drain";
+    let mut covered = window.min(TRAFFIC_RETENTION);
+
+    // Without a log drain for the app, fall back to its recent router logs
+    if hits.is_empty() {
+        let log_text = fetch_logs(ctx, &app_name, Some(String::from("router")), MAX_LOG_LINES)?;
+        let log_lines: Vec<_> = log_text
+            .lines()
+            .filter_map(parse_log_session_line)
+            .collect();
+
+        covered = window;
+
+        // Heroku only returns the latest lines, which
+        // may not go back as far as the window
+        if log_lines.len() >= MAX_LOG_LINES {
+            if let Some((oldest, _)) = log_lines.first() {
+                covered = covered.min((now - *oldest).to_std().unwrap_or_default());
+            }
+        }
+
+        hits = log_lines
+            .into_iter()
+            .filter(|(at, _)| *at >= since)
+            .filter_map(|(at, line)| RouterHit::from_line(at, &line))
+            .collect();
+        source = "the latest router logs";
+    }
 
-    if final_build_info.status != "succeeded" {
+    if hits.is_empty() {
         msg.reply(
-            &ctx,
+            ctx,
             format!(
-                "There was a problem with build {} for {}, cancelling release. Please check the build output.",
-                &build.id, &app_name
+                "There were no requests to {} in the last {} ({})",
+                app_name,
+                format_duration(covered),
+                source
             ),
         )?;
 
         return Ok(());
     }
 
-    msg.reply(
-        ctx.clone(),
-        format!(
-            "App {} commit {} has successfully been released!",
-            &app_name, git_sha,
-        ),
-    )?;
+    let blocked_ips_set = current_blocked_ips(ctx, &app_name)?;
 
-    Ok(())
-}
+    let mut response = format!(
+        "Here are the top client IPs for {} in the last {} ({} requests from {})\n",
+        app_name,
+        format_duration(covered),
+        hits.len(),
+        source
+    );
 
-fn app_info_response(app: heroku_rs::endpoints::apps::App) -> String {
-    format!(
+    if covered < window {
+        response.push_str(&format!(
+            "Only the last {} of the {} you asked for is available from {}\n",
+            format_duration(covered),
+            format_duration(window),
+            source
+        ));
+    }
+
+    for (rank, traffic) in rank_ips(&hits, TOP_IPS_LIMIT).iter().enumerate() {
+        let suggestion = if let Some(entry) = blocking_entry(&traffic.ip, &blocked_ips_set) {
+            format!("already blocked by {}", entry)
+        } else {
+            format!("`!block_ip {} {}`", app_name, traffic.ip)
+        };
+
+        response.push_str(&format!(
+            "\n{}. {}: {} requests, {:.0}% errors, mostly {}\n{}\n",
+            rank + 1,
+            traffic.ip,
+            traffic.requests,
+            traffic.error_rate() * 100.0,
+            traffic.top_path,
+            suggestion
+        ));
+    }
+
+    reply_in_chunks(ctx, msg, &response)?;
+
+    Ok(())
+}
+
+#[command]
+#[min_args(2)]
+#[description = "Scales formations of dynos within a Heroku application. \
+The quantity can be absolute (3 or web=3) or relative (+2, -1), and either the quantity \
+or the size can be left out to keep the current one. \
+Several formations can be scaled together, like web=4:standard-2X worker=2"]
+#[example = "~scale_app your_app_name_or_id name_of_formation [number_of_dynos_to_scale_to] [size_of_dyno]"]
+#[example = "~scale_app my_app web 3 standard-1X"]
+#[example = "~scale_app my_app web +2"]
+#[example = "~scale_app my_app web=3"]
+#[example = "~scale_app my_app worker standard-2X"]
+#[example = "~scale_app my_app web=4:standard-2X worker=2"]
+pub fn scale_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut scale_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+
+    let confirmed = take_flag(&mut scale_args, "--confirm");
+
+    if scale_args.is_empty() {
+        msg.reply(&ctx, "You must include an app name")?;
+
+        return Ok(());
+    }
+
+    let app_name = scale_args.remove(0);
+    let changes = parse_scale_changes(&scale_args)?;
+
+    let current_formations = heroku_client(ctx).request(&formations::FormationList {
+        app_id: app_name.clone(),
+    })?;
+
+    let config = bot_config(ctx);
+    let mut planned = Vec::new();
+
+    for change in changes {
+        let current = current_formations
+            .iter()
+            .find(|formation| formation.r#type == change.formation)
+            .ok_or_else(|| {
+                format!(
+                    "{} does not have a {} formation",
+                    app_name, change.formation
+                )
+            })?;
+
+        let quantity = match &change.quantity {
+            Some(quantity) => Some(quantity.resolve(current.quantity)?),
+            None => None,
+        };
+
+        check_scaling_limits(&config, &app_name, quantity, change.size.as_deref())?;
+
+        let taking_offline = quantity.is_some_and(|quantity| {
+            needs_confirmation(&config, &app_name, &change.formation, quantity)
+        });
+
+        if taking_offline && !confirmed {
+            msg.reply(
+                ctx,
+                format!(
+                    "{} is a production app, scaling its {} formation to 0 will take it offline. \
+                    To scale it down anyway, run `!scale_app {} {} --confirm`",
+                    app_name,
+                    change.formation,
+                    app_name,
+                    scale_args.join(" ")
+                ),
+            )?;
+
+            return Ok(());
+        }
+
+        planned.push((change, current, quantity));
+    }
+
+    warn_if_deploying(ctx, msg, &app_name)?;
+
+    if planned.len() == 1 {
+        let (change, before, quantity) = planned.remove(0);
+
+        let formation = heroku_client(ctx).request(&formations::FormationUpdate {
+            app_id: app_name.clone(),
+            formation_id: change.formation,
+            params: formations::FormationUpdateParams {
+                quantity,
+                size: change.size,
+            },
+        })?;
+
+        msg.reply(
+            ctx,
+            formation_updated_response(app_name, before, formation, &config.dyno_prices),
+        )?;
+
+        return Ok(());
+    }
+
+    // Several formations are changed together in one request,
+    // so either all of them are scaled or none are
+    let updated = heroku_client(ctx).request(&formation_batch_update(
+        &app_name,
+        planned
+            .iter()
+            .map(|(change, _, quantity)| FormationBatchEntry {
+                formation: change.formation.clone(),
+                quantity: *quantity,
+                size: change.size.clone(),
+            })
+            .collect(),
+    ))?;
+    let updated: Vec<formations::Formation> = serde_json::from_value(updated)?;
+
+    let formation_changes: Vec<FormationChange> = planned
+        .iter()
+        .filter_map(|(change, before, _)| {
+            let after = updated
+                .iter()
+                .find(|formation| formation.r#type == change.formation)?;
+
+            Some(FormationChange {
+                formation: change.formation.clone(),
+                before: (before.quantity, before.size.clone()),
+                after: (after.quantity, after.size.clone()),
+            })
+        })
+        .collect();
+
+    let prices = &config.dyno_prices;
+    let cost_before = total_cost(
+        formation_changes
+            .iter()
+            .map(|change| formation_cost(prices, change.before.0, &change.before.1)),
+    );
+    let cost_after = total_cost(
+        formation_changes
+            .iter()
+            .map(|change| formation_cost(prices, change.after.0, &change.after.1)),
+    );
+
+    msg.reply(
+        ctx,
+        format!(
+            "App {}'s formations have been updated\n{}\nMonthly Cost: {}",
+            app_name,
+            formation_changes_table(&formation_changes),
+            cost_change(cost_before, cost_after)
+        ),
+    )?;
+
+    Ok(())
+}
+
+const DEFAULT_RELEASE_LIMIT: usize = 10;
+
+// Get app by name or id
+#[command]
+#[min_args(1)]
+#[description = "Get a list of the most recent releases for a Heroku app. \
+Use --limit to change how many are shown (10 by default), --since to only show releases \
+newer than a duration (like 2d) and --status to only show releases with a status"]
+#[example = "~get_app_releases app_name_or_id [--limit 10] [--since 2d] [--status failed]"]
+#[example = "~get_app_releases my_app"]
+#[example = "~get_app_releases my_app --limit 5 --since 12h --status succeeded"]
+pub fn get_app_releases(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut release_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+
+    let limit = match take_flag_value(&mut release_args, "--limit")? {
+        Some(limit) => limit
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid limit: {}", limit))?,
+        None => DEFAULT_RELEASE_LIMIT,
+    };
+
+    let since = match take_flag_value(&mut release_args, "--since")? {
+        Some(since) => {
+            let since_duration = chrono::Duration::from_std(parse_duration(&since)?)?;
+
+            Some(
+                Utc::now()
+                    .checked_sub_signed(since_duration)
+                    .ok_or_else(|| format!("Invalid --since: {} is too long ago", since))?,
+            )
+        }
+        None => None,
+    };
+
+    let status = take_flag_value(&mut release_args, "--status")?;
+
+    if release_args.len() != 1 {
+        msg.reply(
+            &ctx,
+            "You must include an app name (and optionally --limit, --since and --status)",
+        )?;
+
+        return Ok(());
+    }
+
+    let app_name = release_args.remove(0);
+
+    let mut releases = newest_releases(ctx, &app_name)?;
+
+    releases.sort_by_key(|release| std::cmp::Reverse(release.version));
+
+    let releases: Vec<(releases::Release, Option<String>)> = releases
+        .into_iter()
+        .filter(|release| {
+            status
+                .as_ref()
+                .is_none_or(|status| &release.status == status)
+        })
+        .filter(|release| {
+            since.is_none_or(|since| {
+                parse_heroku_time(&release.created_at).is_some_and(|created_at| created_at >= since)
+            })
+        })
+        .take(limit)
+        .map(|release| {
+            let commit = release_commit(ctx, &app_name, &release);
+            (release, commit)
+        })
+        .collect();
+
+    reply_in_chunks(ctx, msg, &releases_response(&bot_config(ctx), releases))?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(3)]
+#[description = "Compare two releases of an app: the commits and pull requests between them, \
+the config vars that changed, and the add-ons that were attached or removed"]
+#[example = "~release_diff app_name_or_id from_version to_version"]
+#[example = "~release_diff my_app v40 v45"]
+#[example = "~release_diff my_app last-good v45"]
+pub fn release_diff(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let first_target = args
+        .single::<String>()
+        .expect("You must include the version to compare from");
+
+    let second_target = args
+        .single::<String>()
+        .expect("You must include the version to compare to");
+
+    let app_releases = newest_releases(ctx, &app_name)?;
+
+    let current_version = app_releases
+        .iter()
+        .find(|release| release.current)
+        .map(|release| release.version)
+        .ok_or_else(|| format!("{} does not have a current release", app_name))?;
+
+    let versions: Vec<(i64, bool)> = app_releases
+        .iter()
+        .map(|release| (release.version, release.status == "succeeded"))
+        .collect();
+
+    let first_version = resolve_release_target(&first_target, current_version, &versions)?;
+    let second_version = resolve_release_target(&second_target, current_version, &versions)?;
+
+    let (from_version, to_version) = if first_version <= second_version {
+        (first_version, second_version)
+    } else {
+        (second_version, first_version)
+    };
+
+    let find_release = |version: i64| {
+        app_releases
+            .iter()
+            .find(|release| release.version == version)
+            .ok_or_else(|| format!("Release v{} does not exist", version))
+    };
+
+    let from = find_release(from_version)?;
+    let to = find_release(to_version)?;
+
+    let config = bot_config(ctx);
+
+    let mut response = format!(
+        "Changes to {} from v{} to v{}\n\nCommits:\n",
+        app_name, from_version, to_version
+    );
+
+    match (
+        release_commit(ctx, &app_name, from),
+        release_commit(ctx, &app_name, to),
+    ) {
+        (Some(from_commit), Some(to_commit)) if from_commit == to_commit => {
+            response.push_str(&format!("Both releases run commit {}\n", from_commit));
+        }
+        (Some(from_commit), Some(to_commit)) => {
+            let github_client = GitHubClient::new(config.github_token.to_string());
+            let messages =
+                github_client.compare_commit_messages(&config, &from_commit, &to_commit)?;
+
+            response.push_str(&format!(
+                "{} commit(s) from {} to {} <{}>\n",
+                messages.len(),
+                from_commit,
+                to_commit,
+                compare_url(&config, &from_commit, &to_commit)
+            ));
+
+            for (number, title) in messages
+                .iter()
+                .filter_map(|message| pull_request_from_commit(message))
+            {
+                response.push_str(&format!(
+                    "#{} {} <{}>\n",
+                    number,
+                    title,
+                    pull_request_url(&config, number)
+                ));
+            }
+        }
+        _ => response.push_str("Unknown, one of the releases was not built from a commit\n"),
+    }
+
+    let changed_keys: BTreeSet<String> = app_releases
+        .iter()
+        .filter(|release| release.version > from_version && release.version <= to_version)
+        .flat_map(|release| changed_config_vars(&release.description))
+        .collect();
+
+    response.push_str("\nConfig vars changed:\n");
+
+    if changed_keys.is_empty() {
+        response.push_str("None\n");
+    } else {
+        response.push_str(&format!(
+            "{}\n",
+            changed_keys.into_iter().collect::<Vec<String>>().join(", ")
+        ));
+    }
+
+    let mut addon_releases: Vec<&releases::Release> = app_releases
+        .iter()
+        .filter(|release| release.version > from_version && release.version <= to_version)
+        .collect();
+    addon_releases.sort_by_key(|release| release.version);
+
+    response.push_str("\nAdd-ons:\n");
+
+    let mut addons_changed = false;
+
+    for release in addon_releases {
+        let change = match addon_change(&release.description) {
+            Some(AddonChange::Attached(attachment)) => format!("Attached {}", attachment),
+            Some(AddonChange::Detached(attachment)) => format!("Detached {}", attachment),
+            None => continue,
+        };
+
+        response.push_str(&format!("v{}: {}\n", release.version, change));
+        addons_changed = true;
+    }
+
+    if !addons_changed {
+        response.push_str("Unchanged\n");
+    }
+
+    reply_in_chunks(ctx, msg, &response)?;
+
+    Ok(())
+}
+
+#[command]
+#[min_args(2)]
+#[max_args(3)]
+#[description = "Rollback an app to the code associated with a previous release. \
+The target can be a version, previous, -N (N releases back) or last-good (the most recent successful release). \
+Shows what would change, pass --confirm to roll back"]
+#[example = "~rollback_app app_name version-to-rollback-to [--confirm]"]
+#[example = "~rollback_app my_app v5"]
+#[example = "~rollback_app my_app previous --confirm"]
+#[example = "~rollback_app my_app -2"]
+#[example = "~rollback_app my_app last-good --confirm"]
+pub fn rollback_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut rollback_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+    let confirmed = take_flag(&mut rollback_args, "--confirm");
+
+    if rollback_args.len() != 2 {
+        msg.reply(
+            &ctx,
+            "You must include an app name and the version to roll back to (and optionally --confirm)",
+        )?;
+
+        return Ok(());
+    }
+
+    let app_name = rollback_args.remove(0);
+    let version_to_rollback_to = rollback_args.remove(0);
+
+    if deploys_blocked(ctx, msg, &app_name)? {
+        return Ok(());
+    }
+
+    let app_releases = newest_releases(ctx, &app_name)?;
+
+    let current = match app_releases.iter().find(|release| release.current) {
+        Some(current) => current,
+        None => {
+            msg.reply(ctx, format!("{} does not have a current release", app_name))?;
+
+            return Ok(());
+        }
+    };
+
+    let versions: Vec<(i64, bool)> = app_releases
+        .iter()
+        .map(|release| (release.version, release.status == "succeeded"))
+        .collect();
+
+    let target_version =
+        resolve_release_target(&version_to_rollback_to, current.version, &versions)?;
+
+    let target = app_releases
+        .iter()
+        .find(|release| release.version == target_version)
+        .ok_or_else(|| format!("Release v{} does not exist", target_version))?;
+
+    let summary = format!(
+        "Current: {}\nTarget: {}",
+        rollback_release_summary(ctx, &app_name, current),
+        rollback_release_summary(ctx, &app_name, target),
+    );
+
+    if !confirmed {
+        msg.reply(
+            ctx,
+            format!(
+                "Rolling back {} would change it to the code at v{}\n{}\n\n\
+                Run `!rollback_app {} {} --confirm` to roll back",
+                app_name, target_version, summary, app_name, version_to_rollback_to
+            ),
+        )?;
+
+        return Ok(());
+    }
+
+    warn_if_deploying(ctx, msg, &app_name)?;
+
+    let new_release = heroku_client(ctx).request(&releases::ReleaseRollback {
+        app_id: app_name.clone(),
+        params: releases::ReleaseRollbackParams {
+            release: target.id.clone(),
+        },
+    })?;
+
+    bot_store(ctx).record(
+        &msg.author.tag(),
+        &app_name,
+        "rollback_app",
+        &format!(
+            "v{} to v{}, creating v{}",
+            current.version, target_version, new_release.version
+        ),
+    )?;
+
+    msg.reply(
+        ctx,
+        format!(
+            "App {} was successfully rolled back to the code at v{}, Heroku created release v{}\n{}",
+            app_name, target_version, new_release.version, summary
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Get all apps associated with your Heroku account"]
+#[example = "~get_apps"]
+pub fn get_apps(ctx: &mut Context, msg: &Message, _args: Args) -> CommandResult {
+    let apps = heroku_client(ctx).request(&apps::AppList {})?;
+
+    msg.reply(ctx, apps_response(apps))?;
+
+    Ok(())
+}
+
+#[command]
+#[max_args(1)]
+#[description = "Estimate the monthly dyno cost of an app, or of every app in the account \
+when no app is given, using the prices in DYNO_PRICES"]
+#[example = "~cost [app_name_or_id]"]
+#[example = "~cost my_app"]
+pub fn cost(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prices = bot_config(ctx).dyno_prices.clone();
+
+    if let Ok(app_name) = args.single::<String>() {
+        let formations = heroku_client(ctx).request(&formations::FormationList {
+            app_id: app_name.clone(),
+        })?;
+
+        let mut response = format!("Here is the monthly dyno cost of {}\n", app_name);
+        response.push_str(&app_formations_response(formations, &prices));
+
+        reply_in_chunks(ctx, msg, &response)?;
+
+        return Ok(());
+    }
+
+    let mut apps = heroku_client(ctx).request(&apps::AppList {})?;
+    apps.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut response = String::from("Here is the monthly dyno cost of your Heroku apps\n\n");
+    let mut app_costs = Vec::new();
+
+    for app in apps {
+        let formations = heroku_client(ctx).request(&formations::FormationList {
+            app_id: app.id.clone(),
+        })?;
+
+        let app_cost = formations_cost(&formations, &prices);
+        response.push_str(&format!("{}: {}\n", app.name, format_cost(app_cost)));
+        app_costs.push(app_cost);
+    }
+
+    response.push_str(&format!(
+        "\nTotal Monthly Cost: {}\n",
+        format_cost(total_cost(app_costs))
+    ));
+
+    reply_in_chunks(ctx, msg, &response)?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[description = "Restart all dynos associated with an app"]
+#[example = "~restart_app app_name_or_id"]
+pub fn restart_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    warn_if_deploying(ctx, msg, &app_name)?;
+
+    let _response = heroku_client(ctx).request(&dynos::DynoAllRestart {
+        app_id: app_name.clone(),
+    })?;
+
+    msg.reply(
+        ctx,
+        format!("All dynos in {} have been restarted.", app_name),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[description = "Clear the build cache of an app, so the next build starts from scratch"]
+#[example = "~clear_build_cache app_name_or_id"]
+#[example = "~clear_build_cache my_app"]
+pub fn clear_build_cache(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args
+        .single::<String>()
+        .expect("You must include an app name");
+
+    let _response = heroku_client(ctx).request(&builds::BuildDelete {
+        app_id: app_name.clone(),
+    })?;
+
+    bot_store(ctx).record(&msg.author.tag(), &app_name, "clear_build_cache", "")?;
+
+    msg.reply(
+        ctx,
+        format!(
+            "The build cache for {} has been cleared, the next build will start from scratch",
+            app_name
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[min_args(2)]
+#[description = "Deploy an app from a github reference (branch name, partial sha, or full sha), \
+a pull request (pr:1234), a tag (tag:v2024.1), or the commit of an earlier release (release:v123). \
+Deploys are refused while CI is failing or pending for the commit unless --force is passed. \
+Pass --buildpack (more than once for several) to override the app's buildpacks for this build"]
+#[example = "~deploy_app app_name_or_id branch_commit_id_or_sha"]
+#[example = "~deploy_app my_app master"]
+#[example = "~deploy_app my_app pr:1234"]
+#[example = "~deploy_app my_app tag:v2024.1"]
+#[example = "~deploy_app my_app release:v123"]
+#[example = "~deploy_app my_app master --force"]
+#[example = "~deploy_app my_app master --buildpack heroku/nodejs --buildpack emk/rust"]
+pub fn deploy_app(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut deploy_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+    let force = take_flag(&mut deploy_args, "--force");
+    let buildpack_flags = take_flag_values(&mut deploy_args, "--buildpack")?;
+
+    if deploy_args.len() != 2 {
+        msg.reply(
+            &ctx,
+            "You must include an app name and a git ref to deploy \
+            (and optionally --force and --buildpack)",
+        )?;
+
+        return Ok(());
+    }
+
+    let app_name = deploy_args.remove(0);
+    let git_ref = deploy_args.remove(0);
+
+    // Buildpacks from the command take precedence over the ones
+    // configured for the app, without either Heroku uses the app's own
+    let buildpacks = if !buildpack_flags.is_empty() {
+        Some(buildpack_flags)
+    } else {
+        bot_config(ctx).app_buildpacks.get(&app_name).cloned()
+    };
+
+    if deploys_blocked(ctx, msg, &app_name)? {
+        return Ok(());
+    }
+
+    let new_github_client = GitHubClient::new(bot_config(ctx).github_token.to_string());
+
+    let git_sha = match parse_deploy_ref(&git_ref)? {
+        DeployRef::Release(version) => {
+            let release = heroku_client(ctx).request(&releases::ReleaseInfo {
+                app_id: app_name.clone(),
+                release_id: version.to_string(),
+            })?;
+
+            release_commit(ctx, &app_name, &release).ok_or_else(|| {
+                format!(
+                    "Release v{} of {} does not have a commit to redeploy",
+                    version, app_name
+                )
+            })?
+        }
+        deploy_ref => new_github_client.resolve_sha(&bot_config(ctx), &deploy_ref)?,
+    };
+
+    msg.reply(&ctx, format!("{} resolved to commit {}", git_ref, git_sha))?;
+
+    let ci_status = new_github_client.ci_status(&bot_config(ctx), &git_sha)?;
+
+    if ci_status.state != CiState::Passing {
+        if !force {
+            msg.reply(
+                &ctx,
+                format!(
+                    "Refusing to deploy commit {} to {}: {}. Pass --force to deploy anyway.",
+                    git_sha,
+                    app_name,
+                    ci_status.summary()
+                ),
+            )?;
+
+            return Ok(());
+        }
+
+        bot_store(ctx).record(
+            &msg.author.tag(),
+            &app_name,
+            "deploy_app --force",
+            &format!("commit {}: {}", git_sha, ci_status.summary()),
+        )?;
+
+        msg.channel_id.say(
+            &ctx,
+            format!(
+                "{} is forcing a deploy of commit {} to {} even though {}",
+                msg.author,
+                git_sha,
+                app_name,
+                ci_status.summary()
+            ),
+        )?;
+    }
+
+    let ticket = bot_deploy_queue(ctx).enqueue(&app_name, &git_ref, &msg.author.tag());
+
+    if ticket.position > 0 {
+        msg.reply(
+            &ctx,
+            format!(
+                "Deploy {} of {} to {} is queued at position {}, behind {} other deploy(s)",
+                ticket.id,
+                git_ref,
+                app_name,
+                ticket.position + 1,
+                ticket.position
+            ),
+        )?;
+    }
+
+    run_in_background(ctx, msg, "deploy_app", move |ctx, msg| {
+        if !ticket.wait_for_turn() {
+            msg.reply(
+                ctx,
+                format!(
+                    "Deploy {} of {} to {} was cancelled",
+                    ticket.id, git_ref, app_name
+                ),
+            )?;
+
+            return Ok(());
+        }
+
+        // A lock or freeze window may have started while this deploy was queued
+        if ticket.position > 0 && deploys_blocked(ctx, msg, &app_name)? {
+            return Ok(());
+        }
+
+        // The download link expires after a few minutes,
+        // so only ask for it right before starting the build
+        let tarball_url = new_github_client.tarball_url(&bot_config(ctx), &git_sha)?;
+        let checksum = new_github_client.tarball_checksum(&tarball_url)?;
+
+        let build = heroku_client(ctx).request(&builds::BuildCreate {
+            app_id: app_name.clone(),
+            params: builds::BuildCreateParams {
+                buildpacks: buildpacks.map(|buildpacks| {
+                    buildpacks
+                        .into_iter()
+                        // Heroku names buildpacks that aren't in the
+                        // registry after their URL, so both take the flag
+                        .map(|buildpack| builds::BuildpackParam {
+                            url: buildpack.clone(),
+                            name: buildpack,
+                        })
+                        .collect()
+                }),
+                source_blob: builds::SourceBlobParam {
+                    checksum: Some(checksum),
+                    url: tarball_url,
+                    version: Some(git_sha.to_string()),
+                },
+            },
+        })?;
+
+        ticket.start_build(&build.id);
+
+        msg.reply(ctx, build_response(&app_name, &build))?;
+
+        let (mut last_check, mut last_reply) = (Instant::now(), Instant::now());
+
+        let build_check_interval = Duration::from_secs(bot_config(ctx).build_check_interval);
+        let build_message_display_interval =
+            Duration::from_secs(bot_config(ctx).build_message_display_interval);
+
+        loop {
+            if let Some(cancelled_by) = ticket.build_cancelled_by() {
+                msg.reply(
+                    ctx,
+                    format!(
+                        "Build {} for {} was cancelled by {}, it will not be released",
+                        &build.id, &app_name, cancelled_by
+                    ),
+                )?;
+
+                return Ok(());
+            }
+
+            if last_check.elapsed() >= build_check_interval {
+                println!("checking build");
+                let build = heroku_client(ctx).request(&builds::BuildDetails {
+                    app_id: app_name.clone(),
+                    build_id: build.clone().id,
+                })?;
+
+                if build.status != "pending" {
+                    break;
+                }
+
+                last_check = Instant::now();
+            }
+
+            if last_reply.elapsed() >= build_message_display_interval {
+                println!("making a reply in the channel");
+                msg.channel_id
+                    .say(ctx, format!("Build {} is still pending...", &build.id))?;
+
+                last_reply = Instant::now();
+            }
+
+            std::thread::sleep(Duration::from_millis(500));
+        }
+
+        // Release the new build
+        let final_build_info_response = heroku_client(ctx).request(&builds::BuildDetails {
+            app_id: app_name.clone(),
+            build_id: build.clone().id,
+        });
+
+        if final_build_info_response.is_err() {
+            msg.reply(
+                ctx,
+                format!(
+                    "Unable to get the final information for build {} for {}, cancelling release",
+                    &build.id, &app_name
+                ),
+            )?;
+
+            return Ok(());
+        }
+
+        let final_build_info = final_build_info_response.unwrap();
+
+        if final_build_info.status != "succeeded" {
+            msg.reply(
+                ctx,
+                format!(
+                    "There was a problem with build {} for {}, cancelling release. Please check the build output.",
+                    &build.id, &app_name
+                ),
+            )?;
+
+            return Ok(());
+        }
+
+        msg.reply(
+            ctx.clone(),
+            format!(
+                "App {} commit {} has successfully been released! Buildpacks used: {}",
+                &app_name,
+                git_sha,
+                build_buildpacks(&final_build_info),
+            ),
+        )?;
+
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn app_info_response(app: heroku_rs::endpoints::apps::App) -> String {
+    format!(
         "\nApp ID: {}\nApp Name: {}\nReleased At: {}\nWeb URL: {}\n\n",
         app.id,
         app.name,
@@ -496,39 +1704,69 @@ fn app_info_response(app: heroku_rs::endpoints::apps::App) -> String {
     )
 }
 
-fn app_formation_response(formation: heroku_rs::endpoints::formations::Formation) -> String {
+fn app_formation_response(
+    formation: &heroku_rs::endpoints::formations::Formation,
+    before: Option<&heroku_rs::endpoints::formations::Formation>,
+    prices: &HashMap<String, f64>,
+) -> String {
+    let cost = formation_cost(prices, formation.quantity, &formation.size);
+
+    let cost_line = match before {
+        Some(before) => cost_change(formation_cost(prices, before.quantity, &before.size), cost),
+        None => format_cost(cost),
+    };
+
     format!(
-        "\nName: {}\nCommand: {}\nQuantity: {}\nSize: {}\n\n",
-        formation.r#type, formation.command, formation.quantity, formation.size,
+        "\nName: {}\nCommand: {}\nQuantity: {}\nSize: {}\nMonthly Cost: {}\n\n",
+        formation.r#type, formation.command, formation.quantity, formation.size, cost_line,
     )
 }
 
 fn app_formations_response(
     formations_list: Vec<heroku_rs::endpoints::formations::Formation>,
+    prices: &HashMap<String, f64>,
 ) -> String {
     let mut list = String::from("\nFormations for this app:\n");
 
-    for formation in formations_list {
-        let formation_info = app_formation_response(formation);
+    for formation in &formations_list {
+        let formation_info = app_formation_response(formation, None, prices);
         list.push_str(&formation_info);
     }
 
+    list.push_str(&format!(
+        "Total Monthly Cost: {}\n",
+        format_cost(formations_cost(&formations_list, prices))
+    ));
+
     list
 }
 
 fn formation_updated_response(
     app_name: String,
+    before: &heroku_rs::endpoints::formations::Formation,
     formation: heroku_rs::endpoints::formations::Formation,
+    prices: &HashMap<String, f64>,
 ) -> String {
     let mut response = format!(
         "App {}'s formation {} has been updated",
         app_name, formation.r#type
     );
 
-    response.push_str(&app_formation_response(formation));
+    response.push_str(&app_formation_response(&formation, Some(before), prices));
     response
 }
 
+fn formations_cost(
+    formations_list: &[heroku_rs::endpoints::formations::Formation],
+    prices: &HashMap<String, f64>,
+) -> Option<f64> {
+    total_cost(
+        formations_list
+            .iter()
+            .map(|formation| formation_cost(prices, formation.quantity, &formation.size)),
+    )
+}
+
 fn apps_response(processed_app_list: Vec<heroku_rs::endpoints::apps::App>) -> String {
     let mut list = String::from("Here are your Heroku apps\n");
 
@@ -541,26 +1779,136 @@ fn apps_response(processed_app_list: Vec<heroku_rs::endpoints::apps::App>) -> St
 }
 
 fn releases_response(
-    processed_release_list: Vec<heroku_rs::endpoints::releases::Release>,
+    config: &Config,
+    processed_release_list: Vec<(heroku_rs::endpoints::releases::Release, Option<String>)>,
 ) -> String {
+    if processed_release_list.is_empty() {
+        return String::from("No releases matched");
+    }
+
     let mut list = String::from("Here are your app releases\n");
 
-    for release in processed_release_list {
-        let release_info = release_info_response(release);
+    for (release, commit) in processed_release_list {
+        let release_info = release_info_response(config, release, commit);
         list.push_str(&release_info);
     }
 
     list
 }
 
-fn release_info_response(release: heroku_rs::endpoints::releases::Release) -> String {
+fn rollback_release_summary(
+    ctx: &Context,
+    app_name: &str,
+    release: &heroku_rs::endpoints::releases::Release,
+) -> String {
+    format!(
+        "v{} (commit {}): {}",
+        release.version,
+        release_commit(ctx, app_name, release).unwrap_or_else(|| String::from("unknown")),
+        release.description
+    )
+}
+
+fn release_info_response(
+    config: &Config,
+    release: heroku_rs::endpoints::releases::Release,
+    commit: Option<String>,
+) -> String {
+    let created_at = match parse_heroku_time(&release.created_at) {
+        Some(created_at) => format!(
+            "{} ({})",
+            relative_time(created_at, Utc::now()),
+            release.created_at
+        ),
+        None => release.created_at.clone(),
+    };
+
+    let commit = match commit {
+        Some(commit) => format!("{} <{}>", commit, commit_url(config, &commit)),
+        None => String::from("none"),
+    };
+
     format!(
-        "ID: {}\nVersion: {}\nStatus: {}\n\n",
-        release.id, release.version, release.status,
+        "ID: {}\nVersion: v{}\nStatus: {}\nCreated: {}\nUser: {}\nDescription: {}\nCommit: {}\n\n",
+        release.id,
+        release.version,
+        release.status,
+        created_at,
+        release.user.email,
+        release.description,
+        commit,
     )
 }
 
-fn heroku_client(ctx: &Context) -> std::sync::Arc<heroku_rs::framework::HttpApiClient> {
+// Heroku formats times like "2020-02-12T00:35:44Z"
+pub fn parse_heroku_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+// Replies with a response that may be longer than a single Discord message
+pub fn reply_in_chunks(ctx: &Context, msg: &Message, response: &str) -> CommandResult {
+    let mut messages = split_message(response, DISCORD_MESSAGE_LIMIT - 100).into_iter();
+
+    if let Some(first) = messages.next() {
+        msg.reply(ctx, first)?;
+    }
+
+    for message in messages {
+        msg.channel_id.say(ctx, message)?;
+    }
+
+    Ok(())
+}
+
+// The app's releases, newest first. Plain ReleaseList requests only get the
+// oldest 200, which leaves out the recent releases of a long-lived app
+pub fn newest_releases(
+    ctx: &Context,
+    app_name: &str,
+) -> Result<Vec<releases::Release>, Box<dyn std::error::Error>> {
+    let releases_client = ctx
+        .data
+        .read()
+        .get::<ReleasesClientKey>()
+        .expect("Expected Releases Client Key")
+        .clone();
+
+    Ok(releases_client.request(&releases::ReleaseList {
+        app_id: app_name.to_string(),
+    })?)
+}
+
+// The release an app is currently running
+pub fn current_release(
+    ctx: &Context,
+    app_name: &str,
+) -> Result<releases::Release, Box<dyn std::error::Error>> {
+    newest_releases(ctx, app_name)?
+        .into_iter()
+        .find(|release| release.current)
+        .ok_or_else(|| format!("{} does not have a current release", app_name).into())
+}
+
+// The commit a release was built from, if it was built from a slug that recorded one
+pub fn release_commit(
+    ctx: &Context,
+    app_name: &str,
+    release: &releases::Release,
+) -> Option<String> {
+    let slug = release.slug.as_ref()?;
+
+    heroku_client(ctx)
+        .request(&slugs::SlugDetails {
+            app_id: app_name.to_string(),
+            slug_id: slug.id.clone(),
+        })
+        .ok()?
+        .commit
+}
+
+pub fn heroku_client(ctx: &Context) -> std::sync::Arc<heroku_rs::framework::HttpApiClient> {
     ctx.data
         .read()
         .get::<HerokuClientKey>()
@@ -568,7 +1916,7 @@ fn heroku_client(ctx: &Context) -> std::sync::Arc<heroku_rs::framework::HttpApiC
         .clone()
 }
 
-fn bot_config(ctx: &Context) -> std::sync::Arc<Config> {
+pub fn bot_config(ctx: &Context) -> std::sync::Arc<Config> {
     ctx.data
         .read()
         .get::<Config>()
@@ -576,45 +1924,68 @@ fn bot_config(ctx: &Context) -> std::sync::Arc<Config> {
         .clone()
 }
 
-fn block_ips_value(config_vars: HashMap<String, Option<String>>) -> String {
-    config_vars
-        .get(&BLOCKED_IPS_ENV_VAR.to_string())
-        .unwrap()
-        .as_ref()
-        .unwrap()
-        .to_string()
+pub fn bot_store(ctx: &Context) -> std::sync::Arc<Store> {
+    ctx.data
+        .read()
+        .get::<Store>()
+        .expect("Expected Store")
+        .clone()
 }
 
-fn blocked_ips_config_var(blocked_ips_set: HashSet<String>) -> HashMap<String, String> {
-    let blocked_ips_set_string = parse_config_value_string(blocked_ips_set);
-    config_var(blocked_ips_set_string)
+pub fn bot_drain_stats(ctx: &Context) -> std::sync::Arc<DrainStats> {
+    ctx.data
+        .read()
+        .get::<DrainStats>()
+        .expect("Expected Drain Stats")
+        .clone()
+}
+
+pub fn bot_config_var_locks(ctx: &Context) -> std::sync::Arc<ConfigVarLocks> {
+    ctx.data
+        .read()
+        .get::<ConfigVarLocks>()
+        .expect("Expected Config Var Locks")
+        .clone()
 }
 
-fn config_var(updated_blocked_ips_value: String) -> HashMap<String, String> {
-    let mut config_var = HashMap::new();
-    config_var.insert(BLOCKED_IPS_ENV_VAR.to_string(), updated_blocked_ips_value);
-    config_var
+pub fn bot_deploy_queue(ctx: &Context) -> std::sync::Arc<DeployQueue> {
+    ctx.data
+        .read()
+        .get::<DeployQueue>()
+        .expect("Expected Deploy Queue")
+        .clone()
 }
 
-fn empty_config_var() -> HashMap<String, String> {
-    let mut config_var = HashMap::new();
-    config_var.insert(BLOCKED_IPS_ENV_VAR.to_string(), "".to_string());
-    config_var
+// Changes an app's BLOCKED_IPS while holding its config var lock,
+// applying the change again if BLOCKED_IPS changed in the meantime
+fn update_blocked_ips<T>(
+    ctx: &Context,
+    app_name: &str,
+    change: impl FnMut(Option<&str>) -> (ConfigVarWrite, T),
+) -> Result<T, Box<dyn Error>> {
+    update_config_var(
+        &*heroku_client(ctx),
+        &bot_config_var_locks(ctx),
+        app_name,
+        BLOCKED_IPS_ENV_VAR,
+        change,
+    )
 }
 
-fn current_blocked_ip_addresses(config_vars: HashMap<String, Option<String>>) -> HashSet<String> {
-    let blocked_ips_value = block_ips_value(config_vars);
-    parse_config_value_set(blocked_ips_value)
+fn current_blocked_ips(ctx: &Context, app_name: &str) -> Result<HashSet<String>, Box<dyn Error>> {
+    let current = heroku_client(ctx).config_var(app_name, BLOCKED_IPS_ENV_VAR)?;
+    Ok(blocked_ips_set(current.as_deref()))
 }
 
-fn null_blocked_ips_config_var() -> HashMap<String, Option<String>> {
-    let mut config_var = HashMap::new();
-    config_var.insert(BLOCKED_IPS_ENV_VAR.to_string(), None);
-    config_var
+fn blocked_ips_set(blocked_ips_value: Option<&str>) -> HashSet<String> {
+    let mut blocked_ips_set =
+        parse_config_value_set(blocked_ips_value.unwrap_or_default().to_string());
+    blocked_ips_set.remove("");
+    blocked_ips_set
 }
 
-fn blocked_ips_exist(config_vars: &HashMap<String, Option<String>>) -> bool {
-    config_vars.get(&BLOCKED_IPS_ENV_VAR.to_string()).is_some()
+fn blocked_ips_value(blocked_ips_set: HashSet<String>) -> String {
+    parse_config_value_string(blocked_ips_set)
 }
 
 fn build_response(app_name: &str, build: &heroku_rs::endpoints::builds::Build) -> String {
@@ -624,20 +1995,13 @@ fn build_response(app_name: &str, build: &heroku_rs::endpoints::builds::Build) -
     )
 }
 
-fn commit_info_url(ctx: &Context, git_ref: String) -> String {
-    format!(
-        "https://api.github.com/repos/{}/{}/commits/{}",
-        bot_config(ctx).github_org,
-        bot_config(ctx).github_repo,
-        git_ref
-    )
-}
-
-fn source_url(ctx: &Context, git_sha: &str) -> String {
-    format!(
-        "https://codeload.github.com/{}/{}/tar.gz/{}",
-        bot_config(ctx).github_org,
-        bot_config(ctx).github_repo,
-        git_sha,
-    )
+fn build_buildpacks(build: &heroku_rs::endpoints::builds::Build) -> String {
+    match &build.buildpacks {
+        Some(buildpacks) if !buildpacks.is_empty() => buildpacks
+            .iter()
+            .map(|buildpack| buildpack.url.clone())
+            .collect::<Vec<String>>()
+            .join(", "),
+        _ => String::from("detected by Heroku"),
+    }
 }
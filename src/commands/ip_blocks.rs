@@ -0,0 +1,102 @@
+use chrono::{Timelike, Utc};
+
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::commands::heroku::{remove_ip_block, IpUnblock};
+use crate::config::Config;
+use crate::config_vars::ConfigVarLocks;
+use crate::ip_blocks::parse_blocked_entry;
+use crate::store::Store;
+use crate::HerokuClientKey;
+
+// Unblocks IP addresses blocked with `block_ip --for` once their time is up,
+// announcing it where they were blocked
+pub fn run_ip_block_expiry(data: Arc<RwLock<ShareMap>>, http: Arc<Http>) {
+    loop {
+        // Wake up at the start of every minute
+        let now = Utc::now();
+        thread::sleep(Duration::from_secs(60 - u64::from(now.second())));
+
+        let (heroku, config, store, locks) = {
+            let data = data.read();
+            (
+                data.get::<HerokuClientKey>()
+                    .expect("Expected Heroku Client Key")
+                    .clone(),
+                data.get::<Config>().expect("Expected Config").clone(),
+                data.get::<Store>().expect("Expected Store").clone(),
+                data.get::<ConfigVarLocks>()
+                    .expect("Expected Config Var Locks")
+                    .clone(),
+            )
+        };
+
+        for (app_name, ip_addr) in store.read(|state| state.expired_ip_blocks(Utc::now())) {
+            let channel_id = store.read(|state| {
+                state
+                    .blocked_ips
+                    .get(&app_name)
+                    .and_then(|blocked_ips| blocked_ips.get(&ip_addr))
+                    .and_then(|blocked_ip| blocked_ip.channel_id)
+            });
+
+            let block = match parse_blocked_entry(&ip_addr) {
+                Ok(block) => block,
+                Err(_) => {
+                    store
+                        .update(|state| state.forget_blocked_ip(&app_name, &ip_addr))
+                        .ok();
+                    continue;
+                }
+            };
+
+            let announcement = match remove_ip_block(&*heroku, &locks, &app_name, &block) {
+                Ok(IpUnblock::Removed { entries, .. }) => {
+                    store
+                        .update(|state| {
+                            for entry in &entries {
+                                state.forget_blocked_ip(&app_name, entry);
+                            }
+                        })
+                        .ok();
+
+                    store
+                        .record("block expiry", &app_name, "unblock_ip", &ip_addr)
+                        .ok();
+
+                    format!(
+                        "The block on IP address {} for {} has expired and it has been unblocked",
+                        ip_addr, app_name
+                    )
+                }
+                // Someone already unblocked it by hand
+                Ok(_) => {
+                    store
+                        .update(|state| state.forget_blocked_ip(&app_name, &ip_addr))
+                        .ok();
+                    continue;
+                }
+                // Leave the block in the store so it is retried next minute
+                Err(error) => {
+                    println!(
+                        "Unable to unblock expired IP address {} for {}: {:?}",
+                        ip_addr, app_name, error
+                    );
+                    continue;
+                }
+            };
+
+            println!("{}", announcement);
+
+            if let Some(channel_id) = channel_id.or(config.announcement_channel_id) {
+                ChannelId(channel_id).say(&http, &announcement).ok();
+            }
+        }
+    }
+}
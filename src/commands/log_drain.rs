@@ -0,0 +1,119 @@
+use chrono::Utc;
+
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use std::io::Read;
+use std::sync::Arc;
+
+use tiny_http::{Method, Response, Server};
+
+use crate::config::Config;
+use crate::log_drain::{parse_drain_body, tokens_match, DrainStats, MAX_DRAIN_BODY};
+
+// Accepts Heroku log drains at /drains/<app name>, counting the errors in
+// them and posting an alert when an error crosses its threshold. Add a drain
+// to an app with `heroku drains:add https://<bot>/drains/<app>?token=<LOG_DRAIN_TOKEN>`
+pub fn run_log_drain(data: Arc<RwLock<ShareMap>>, http: Arc<Http>) {
+    let (config, stats) = {
+        let data = data.read();
+        (
+            data.get::<Config>().expect("Expected Config").clone(),
+            data.get::<DrainStats>()
+                .expect("Expected Drain Stats")
+                .clone(),
+        )
+    };
+
+    let address = match &config.log_drain_address {
+        Some(address) => address.clone(),
+        None => return,
+    };
+
+    // Anyone who can reach the endpoint could otherwise fill the error
+    // counts and set off alerts, so drains are only accepted with a token
+    let token = match &config.log_drain_token {
+        Some(token) => token.clone(),
+        None => {
+            println!("LOG_DRAIN_TOKEN is not set, not accepting log drains");
+            return;
+        }
+    };
+
+    let server = Server::http(&address).expect("Err starting the log drain endpoint");
+    println!("Accepting log drains on {}", address);
+
+    for mut request in server.incoming_requests() {
+        let app_name = match drain_app_name(&token, request.method(), request.url()) {
+            Some(app_name) => app_name,
+            None => {
+                request.respond(Response::empty(404)).ok();
+                continue;
+            }
+        };
+
+        let mut body = String::new();
+
+        if request
+            .as_reader()
+            .take(MAX_DRAIN_BODY + 1)
+            .read_to_string(&mut body)
+            .is_err()
+        {
+            request.respond(Response::empty(400)).ok();
+            continue;
+        }
+
+        if body.len() as u64 > MAX_DRAIN_BODY {
+            request.respond(Response::empty(413)).ok();
+            continue;
+        }
+
+        // Heroku doesn't wait long for a response, so answer before alerting
+        request.respond(Response::empty(204)).ok();
+
+        let alerts = stats.record(
+            &app_name,
+            &parse_drain_body(&body),
+            &config.log_alert_thresholds,
+            config.log_alert_window,
+            Utc::now(),
+        );
+
+        for alert in alerts {
+            println!("Log drain alert: {}", alert.message());
+
+            if let Some(channel_id) = config.alert_channel_id.or(config.announcement_channel_id) {
+                ChannelId(channel_id)
+                    .say(&http, format!(":rotating_light: {}", alert.message()))
+                    .ok();
+            }
+        }
+    }
+}
+
+// The app a drain request is for, if it is a POST to /drains/<app name>
+// with the right token
+fn drain_app_name(token: &str, method: &Method, url: &str) -> Option<String> {
+    if *method != Method::Post {
+        return None;
+    }
+
+    let mut parts = url.splitn(2, '?');
+    let path = parts.next().unwrap_or_default();
+    let query = parts.next().unwrap_or_default();
+
+    let token_matches = query
+        .split('&')
+        .filter_map(|param| param.strip_prefix("token="))
+        .any(|given| tokens_match(given, token));
+
+    if !token_matches {
+        return None;
+    }
+
+    path.strip_prefix("/drains/")
+        .filter(|app_name| !app_name.is_empty() && !app_name.contains('/'))
+        .map(String::from)
+}
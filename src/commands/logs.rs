@@ -0,0 +1,123 @@
+use heroku_rs::endpoints::logs;
+use heroku_rs::framework::apiclient::HerokuApiClient;
+
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use std::error::Error;
+
+use crate::commands::heroku::heroku_client;
+use crate::logs::{filter_log_lines, logs_reply, LogsReply, MAX_LOG_LINES};
+use crate::utilities::*;
+
+const DEFAULT_LOG_LINES: usize = 100;
+
+#[command]
+#[min_args(1)]
+#[max_args(7)]
+#[description = "Show the most recent log lines of an app. Use --dyno to only show the logs \
+of a dyno or formation, --lines to change how many lines are shown (100 by default) and \
+--grep to only show lines containing a pattern"]
+#[example = "~logs app_name_or_id [--dyno web] [--lines 100] [--grep pattern]"]
+#[example = "~logs my_app"]
+#[example = "~logs my_app --dyno web.1 --lines 50 --grep code=H12"]
+pub fn logs(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut logs_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+
+    let dyno = take_flag_value(&mut logs_args, "--dyno")?;
+
+    let lines = match take_flag_value(&mut logs_args, "--lines")? {
+        Some(lines) => lines
+            .parse::<usize>()
+            .ok()
+            .filter(|lines| *lines > 0 && *lines <= MAX_LOG_LINES)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid number of lines: {} (must be between 1 and {})",
+                    lines, MAX_LOG_LINES
+                )
+            })?,
+        None => DEFAULT_LOG_LINES,
+    };
+
+    let pattern = take_flag_value(&mut logs_args, "--grep")?;
+
+    if logs_args.len() != 1 {
+        msg.reply(
+            &ctx,
+            "You must include an app name (and optionally --dyno, --lines and --grep)",
+        )?;
+
+        return Ok(());
+    }
+
+    let app_name = logs_args.remove(0);
+
+    // When filtering, fetch as many lines as Heroku allows
+    // so there is more to search through
+    let requested_lines = if pattern.is_some() {
+        MAX_LOG_LINES
+    } else {
+        lines
+    };
+
+    let log_text = fetch_logs(ctx, &app_name, dyno.clone(), requested_lines)?;
+    let log_lines = filter_log_lines(&log_text, pattern.as_deref(), lines);
+
+    let description = format!(
+        "{}{}{}",
+        app_name,
+        dyno.map(|dyno| format!(" ({})", dyno)).unwrap_or_default(),
+        pattern
+            .map(|pattern| format!(" matching `{}`", pattern))
+            .unwrap_or_default()
+    );
+
+    if log_lines.is_empty() {
+        msg.reply(ctx, format!("There are no recent logs for {}", description))?;
+
+        return Ok(());
+    }
+
+    let header = format!(
+        "Here are the last {} log lines for {}",
+        log_lines.len(),
+        description
+    );
+
+    match logs_reply(&header, &log_lines) {
+        LogsReply::CodeBlock(message) => {
+            msg.reply(ctx, message)?;
+        }
+        LogsReply::Attachment(logs) => {
+            let file_name = format!("{}-logs.txt", app_name);
+
+            msg.channel_id.send_files(
+                &ctx.http,
+                vec![(logs.as_bytes(), file_name.as_str())],
+                |m| m.content(format!("{}: {}", msg.author.mention(), header)),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// Creates a log session for an app and downloads its lines
+pub fn fetch_logs(
+    ctx: &Context,
+    app_name: &str,
+    dyno: Option<String>,
+    lines: usize,
+) -> Result<String, Box<dyn Error>> {
+    let session = heroku_client(ctx).request(&logs::LogSessionCreate::new(
+        app_name,
+        dyno.as_deref(),
+        Some(lines as i64),
+        None,
+        Some(false),
+    ))?;
+
+    Ok(reqwest::blocking::get(&session.logplex_url)?.text()?)
+}
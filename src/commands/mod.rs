@@ -1,4 +1,10 @@
+pub mod deploys;
+pub mod dynos;
 pub mod heroku;
+pub mod ip_blocks;
+pub mod log_drain;
+pub mod logs;
 pub mod math;
 pub mod myid;
 pub mod ping;
+pub mod schedules;
@@ -0,0 +1,308 @@
+use chrono::{Timelike, Utc};
+
+use heroku_rs::endpoints::formations;
+use heroku_rs::framework::apiclient::HerokuApiClient;
+
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::commands::heroku::{bot_config, bot_store, reply_in_chunks};
+use crate::config::Config;
+use crate::scaling::{check_scaling_limits, needs_confirmation};
+use crate::schedules::{next_schedule_id, ScalingSchedule};
+use crate::store::Store;
+use crate::utilities::*;
+use crate::HerokuClientKey;
+
+#[command]
+#[min_args(7)]
+#[max_args(8)]
+#[description = "Scale a formation of an app on a cron-like schedule (5 fields, in UTC)"]
+#[example = "~schedule_scale app_name_or_id formation=quantity[:size] minute hour day month weekday"]
+#[example = "~schedule_scale my_app web=4:standard-2X 0 8 * * 1-5"]
+#[example = "~schedule_scale my_app web=2 0 20 * * *"]
+pub fn schedule_scale(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut schedule_args: Vec<String> = args.iter::<String>().filter_map(Result::ok).collect();
+
+    let confirmed = take_flag(&mut schedule_args, "--confirm");
+
+    let store = bot_store(ctx);
+
+    // The id is only picked once the schedule is saved
+    let mut schedule = ScalingSchedule::parse(
+        "",
+        &schedule_args.join(" "),
+        &msg.author.tag(),
+        Some(msg.channel_id.0),
+    )?;
+
+    let config = bot_config(ctx);
+
+    check_scaling_limits(
+        &config,
+        &schedule.app_name,
+        Some(schedule.quantity),
+        schedule.size.as_deref(),
+    )?;
+
+    if needs_confirmation(
+        &config,
+        &schedule.app_name,
+        &schedule.formation,
+        schedule.quantity,
+    ) && !confirmed
+    {
+        msg.reply(
+            ctx,
+            format!(
+                "{} is a production app, scaling its {} formation to 0 will take it offline. \
+                To schedule it anyway, run `!schedule_scale {} --confirm`",
+                schedule.app_name,
+                schedule.formation,
+                schedule_args.join(" ")
+            ),
+        )?;
+
+        return Ok(());
+    }
+
+    // Picking the id while the store is locked keeps two schedules
+    // created at the same time from getting the same one
+    let schedule = store.update(|state| {
+        schedule.id = next_schedule_id(&state.scaling_schedules);
+        state.scaling_schedules.push(schedule.clone());
+        schedule
+    })?;
+
+    store.record(
+        &msg.author.tag(),
+        &schedule.app_name,
+        "schedule_scale",
+        &format!(
+            "schedule {}: {} to {} at {}",
+            schedule.id,
+            schedule.formation,
+            schedule.target(),
+            schedule.cron
+        ),
+    )?;
+
+    msg.reply(
+        ctx,
+        format!(
+            "Schedule {} will scale {}'s {} formation to {} at `{}` (UTC)",
+            schedule.id,
+            schedule.app_name,
+            schedule.formation,
+            schedule.target(),
+            schedule.cron
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[max_args(1)]
+#[description = "List the scaling schedules, from the config and created with schedule_scale"]
+#[example = "~list_schedules [app_name_or_id]"]
+#[example = "~list_schedules my_app"]
+pub fn list_schedules(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let app_name = args.single::<String>().ok();
+
+    let schedules: Vec<(ScalingSchedule, bool)> = all_schedules(&bot_config(ctx), &bot_store(ctx))
+        .into_iter()
+        .filter(|(schedule, _)| {
+            app_name
+                .as_ref()
+                .is_none_or(|app_name| &schedule.app_name == app_name)
+        })
+        .collect();
+
+    if schedules.is_empty() {
+        msg.reply(ctx, "There are no scaling schedules")?;
+
+        return Ok(());
+    }
+
+    let mut response = String::from("Here are the scaling schedules (in UTC)\n");
+
+    for (schedule, paused) in schedules {
+        response.push_str(&format!(
+            "\nID: {}\nApp: {}\nFormation: {}\nScale To: {}\nWhen: {}\nCreated By: {}\nState: {}\n",
+            schedule.id,
+            schedule.app_name,
+            schedule.formation,
+            schedule.target(),
+            schedule.cron,
+            schedule.created_by,
+            if paused { "paused" } else { "active" }
+        ));
+    }
+
+    reply_in_chunks(ctx, msg, &response)?;
+
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+#[description = "Stop a scaling schedule from being applied until it is resumed"]
+#[example = "~pause_schedule schedule_id"]
+#[example = "~pause_schedule config-1"]
+pub fn pause_schedule(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    set_schedule_paused(ctx, msg, &args.single::<String>()?, true)
+}
+
+#[command]
+#[num_args(1)]
+#[description = "Start applying a paused scaling schedule again"]
+#[example = "~resume_schedule schedule_id"]
+#[example = "~resume_schedule config-1"]
+pub fn resume_schedule(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    set_schedule_paused(ctx, msg, &args.single::<String>()?, false)
+}
+
+fn set_schedule_paused(ctx: &Context, msg: &Message, id: &str, paused: bool) -> CommandResult {
+    let store = bot_store(ctx);
+
+    let schedule = match all_schedules(&bot_config(ctx), &store)
+        .into_iter()
+        .find(|(schedule, _)| schedule.id == id)
+    {
+        Some((schedule, _)) => schedule,
+        None => {
+            msg.reply(
+                ctx,
+                format!("There is no scaling schedule with the ID {}", id),
+            )?;
+
+            return Ok(());
+        }
+    };
+
+    store.update(|state| {
+        if paused {
+            state.paused_schedules.insert(id.to_string());
+        } else {
+            state.paused_schedules.remove(id);
+        }
+    })?;
+
+    let action = if paused { "paused" } else { "resumed" };
+
+    store.record(
+        &msg.author.tag(),
+        &schedule.app_name,
+        &format!("{}_schedule", action),
+        id,
+    )?;
+
+    msg.reply(
+        ctx,
+        format!(
+            "Schedule {} ({}'s {} formation to {} at `{}`) has been {}",
+            id,
+            schedule.app_name,
+            schedule.formation,
+            schedule.target(),
+            schedule.cron,
+            action
+        ),
+    )?;
+
+    Ok(())
+}
+
+// Every schedule from the config and the store, with whether it is paused
+fn all_schedules(config: &Config, store: &Store) -> Vec<(ScalingSchedule, bool)> {
+    store.read(|state| {
+        config
+            .scaling_schedules
+            .iter()
+            .chain(state.scaling_schedules.iter())
+            .map(|schedule| {
+                let paused = state.paused_schedules.contains(&schedule.id);
+                (schedule.clone(), paused)
+            })
+            .collect()
+    })
+}
+
+// Runs forever, applying every active schedule at the minutes it matches
+pub fn run_scaling_schedules(data: Arc<RwLock<ShareMap>>, http: Arc<Http>) {
+    loop {
+        // Wake up at the start of every minute
+        let now = Utc::now();
+        thread::sleep(Duration::from_secs(60 - u64::from(now.second())));
+
+        let now = Utc::now();
+
+        let (heroku, config, store) = {
+            let data = data.read();
+            (
+                data.get::<HerokuClientKey>()
+                    .expect("Expected Heroku Client Key")
+                    .clone(),
+                data.get::<Config>().expect("Expected Config").clone(),
+                data.get::<Store>().expect("Expected Store").clone(),
+            )
+        };
+
+        for (schedule, paused) in all_schedules(&config, &store) {
+            if paused || !schedule.is_due(now) {
+                continue;
+            }
+
+            let response = heroku.request(&formations::FormationUpdate {
+                app_id: schedule.app_name.clone(),
+                formation_id: schedule.formation.clone(),
+                params: formations::FormationUpdateParams {
+                    quantity: Some(schedule.quantity),
+                    size: schedule.size.clone(),
+                },
+            });
+
+            let announcement = match response {
+                Ok(formation) => {
+                    store
+                        .record(
+                            &format!("schedule {}", schedule.id),
+                            &schedule.app_name,
+                            "scale_app",
+                            &format!(
+                                "{} to {} x {}",
+                                formation.r#type, formation.quantity, formation.size
+                            ),
+                        )
+                        .ok();
+
+                    format!(
+                        "Scaling schedule {} scaled {}'s {} formation to {} x {}",
+                        schedule.id,
+                        schedule.app_name,
+                        formation.r#type,
+                        formation.quantity,
+                        formation.size
+                    )
+                }
+                Err(error) => format!(
+                    "Scaling schedule {} was unable to scale {}'s {} formation: {:?}",
+                    schedule.id, schedule.app_name, schedule.formation, error
+                ),
+            };
+
+            println!("{}", announcement);
+
+            if let Some(channel_id) = schedule.channel_id.or(config.announcement_channel_id) {
+                ChannelId(channel_id).say(&http, &announcement).ok();
+            }
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use crate::deploy_locks::FreezeWindow;
+use crate::schedules::ScalingSchedule;
 use serenity::prelude::TypeMapKey;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -10,6 +14,20 @@ pub struct Config {
     pub github_org: String,
     pub github_repo: String,
     pub github_token: String,
+    pub state_file: String,
+    pub freeze_windows: Vec<FreezeWindow>,
+    pub app_buildpacks: HashMap<String, Vec<String>>,
+    pub app_max_dynos: HashMap<String, i32>,
+    pub app_dyno_sizes: HashMap<String, Vec<String>>,
+    pub production_apps: HashSet<String>,
+    pub scaling_schedules: Vec<ScalingSchedule>,
+    pub announcement_channel_id: Option<u64>,
+    pub dyno_prices: HashMap<String, f64>,
+    pub log_drain_address: Option<String>,
+    pub log_drain_token: Option<String>,
+    pub alert_channel_id: Option<u64>,
+    pub log_alert_thresholds: HashMap<String, usize>,
+    pub log_alert_window: Duration,
 }
 
 impl Config {
@@ -30,6 +48,30 @@ impl Config {
             github_org,
             github_repo,
             github_token,
+            state_file: String::from("ops-bot-state.json"),
+            freeze_windows: Vec::new(),
+            app_buildpacks: HashMap::new(),
+            app_max_dynos: HashMap::new(),
+            app_dyno_sizes: HashMap::new(),
+            production_apps: HashSet::new(),
+            scaling_schedules: Vec::new(),
+            announcement_channel_id: None,
+            dyno_prices: HashMap::new(),
+            log_drain_address: None,
+            log_drain_token: None,
+            alert_channel_id: None,
+            log_alert_thresholds: [
+                ("H12", 10),
+                ("H13", 5),
+                ("H14", 1),
+                ("R14", 5),
+                ("R15", 1),
+                ("5xx", 50),
+            ]
+            .iter()
+            .map(|(code, threshold)| (code.to_string(), *threshold))
+            .collect(),
+            log_alert_window: Duration::from_secs(5 * 60),
         }
     }
 }
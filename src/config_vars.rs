@@ -0,0 +1,284 @@
+use serenity::prelude::TypeMapKey;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+// The config var requests behind a read-modify-write, so the retry logic
+// can be tested against a mock instead of Heroku
+pub trait ConfigVarClient {
+    // The value of a config var, or None when the app doesn't have it
+    fn config_var(&self, app_name: &str, key: &str) -> Result<Option<String>, Box<dyn Error>>;
+
+    fn set_config_var(&self, app_name: &str, key: &str, value: &str) -> Result<(), Box<dyn Error>>;
+
+    fn delete_config_var(&self, app_name: &str, key: &str) -> Result<(), Box<dyn Error>>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigVarWrite {
+    Unchanged,
+    Set(String),
+    Delete,
+}
+
+// Heroku refuses to store more than this in an app's config vars,
+// so no single config var can be any bigger
+pub const MAX_CONFIG_VARS_SIZE: usize = 32 * 1024;
+
+// How many times a change is applied to a freshly read value before giving
+// up on a config var that keeps changing underneath it
+pub const MAX_CONFIG_VAR_ATTEMPTS: usize = 3;
+
+// One lock per app, so commands changing the same app's config vars
+// take turns instead of overwriting each other's changes
+#[derive(Debug, Default)]
+pub struct ConfigVarLocks {
+    apps: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl ConfigVarLocks {
+    pub fn app_lock(&self, app_name: &str) -> Arc<Mutex<()>> {
+        self.apps
+            .lock()
+            .unwrap()
+            .entry(app_name.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+impl TypeMapKey for ConfigVarLocks {
+    type Value = Arc<ConfigVarLocks>;
+}
+
+// Applies a change to the current value of a config var and writes the
+// result back, holding the app's lock throughout. Heroku has no way to make
+// a write conditional, so the value is read again right before writing and
+// the change is applied again if someone changed it outside the bot
+pub fn update_config_var<C, T, F>(
+    client: &C,
+    locks: &ConfigVarLocks,
+    app_name: &str,
+    key: &str,
+    mut change: F,
+) -> Result<T, Box<dyn Error>>
+where
+    C: ConfigVarClient + ?Sized,
+    F: FnMut(Option<&str>) -> (ConfigVarWrite, T),
+{
+    let app_lock = locks.app_lock(app_name);
+    let _guard = app_lock.lock().unwrap();
+
+    for _ in 0..MAX_CONFIG_VAR_ATTEMPTS {
+        let current = client.config_var(app_name, key)?;
+        let (write, result) = change(current.as_deref());
+
+        if write == ConfigVarWrite::Unchanged {
+            return Ok(result);
+        }
+
+        if client.config_var(app_name, key)? != current {
+            continue;
+        }
+
+        match write {
+            ConfigVarWrite::Set(value) => client.set_config_var(app_name, key, &value)?,
+            ConfigVarWrite::Delete => client.delete_config_var(app_name, key)?,
+            ConfigVarWrite::Unchanged => {}
+        }
+
+        return Ok(result);
+    }
+
+    Err(format!(
+        "{} kept changing for {} while updating it, please try again",
+        key, app_name
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+    use std::time::Duration;
+
+    const KEY: &str = "BLOCKED_IPS";
+
+    // An app's config vars, with writes from "someone else" that land
+    // right after a given number of reads
+    #[derive(Default)]
+    struct MockHeroku {
+        value: Mutex<Option<String>>,
+        reads: Mutex<usize>,
+        writes: Mutex<usize>,
+        interference: Mutex<Vec<(usize, Option<String>)>>,
+        read_delay: Option<Duration>,
+    }
+
+    impl MockHeroku {
+        fn with_value(value: &str) -> MockHeroku {
+            MockHeroku {
+                value: Mutex::new(Some(value.to_string())),
+                ..MockHeroku::default()
+            }
+        }
+
+        fn interfere_after_read(&self, read: usize, value: &str) {
+            self.interference
+                .lock()
+                .unwrap()
+                .push((read, Some(value.to_string())));
+        }
+
+        fn value(&self) -> Option<String> {
+            self.value.lock().unwrap().clone()
+        }
+    }
+
+    impl ConfigVarClient for MockHeroku {
+        fn config_var(&self, _app_name: &str, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+            assert_eq!(key, KEY);
+
+            let current = self.value();
+
+            if let Some(delay) = self.read_delay {
+                thread::sleep(delay);
+            }
+
+            let mut reads = self.reads.lock().unwrap();
+            *reads += 1;
+
+            for (read, value) in self.interference.lock().unwrap().iter() {
+                if *read == *reads {
+                    *self.value.lock().unwrap() = value.clone();
+                }
+            }
+
+            Ok(current)
+        }
+
+        fn set_config_var(
+            &self,
+            _app_name: &str,
+            _key: &str,
+            value: &str,
+        ) -> Result<(), Box<dyn Error>> {
+            *self.writes.lock().unwrap() += 1;
+            *self.value.lock().unwrap() = Some(value.to_string());
+            Ok(())
+        }
+
+        fn delete_config_var(&self, _app_name: &str, _key: &str) -> Result<(), Box<dyn Error>> {
+            *self.writes.lock().unwrap() += 1;
+            *self.value.lock().unwrap() = None;
+            Ok(())
+        }
+    }
+
+    fn add_ip(ip: &'static str) -> impl FnMut(Option<&str>) -> (ConfigVarWrite, ()) {
+        move |current| {
+            let mut ips: Vec<&str> = current
+                .unwrap_or_default()
+                .split(',')
+                .filter(|ip| !ip.is_empty())
+                .collect();
+            ips.push(ip);
+
+            (ConfigVarWrite::Set(ips.join(",")), ())
+        }
+    }
+
+    #[test]
+    fn write_the_changed_value() {
+        let heroku = MockHeroku::default();
+        let locks = ConfigVarLocks::default();
+
+        update_config_var(&heroku, &locks, "my_app", KEY, add_ip("1.1.1.1")).unwrap();
+        assert_eq!(heroku.value().unwrap(), "1.1.1.1");
+
+        update_config_var(&heroku, &locks, "my_app", KEY, |_| {
+            (ConfigVarWrite::Delete, ())
+        })
+        .unwrap();
+        assert!(heroku.value().is_none());
+    }
+
+    #[test]
+    fn skip_writing_unchanged_values() {
+        let heroku = MockHeroku::with_value("1.1.1.1");
+        let locks = ConfigVarLocks::default();
+
+        let seen = update_config_var(&heroku, &locks, "my_app", KEY, |current| {
+            (ConfigVarWrite::Unchanged, current.map(String::from))
+        })
+        .unwrap();
+
+        assert_eq!(seen.unwrap(), "1.1.1.1");
+        assert_eq!(*heroku.writes.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn reapply_the_change_after_a_concurrent_write() {
+        let heroku = MockHeroku::with_value("1.1.1.1");
+        let locks = ConfigVarLocks::default();
+
+        // Someone blocks 2.2.2.2 after the bot reads the value but before it writes
+        heroku.interfere_after_read(1, "1.1.1.1,2.2.2.2");
+
+        update_config_var(&heroku, &locks, "my_app", KEY, add_ip("3.3.3.3")).unwrap();
+
+        assert_eq!(heroku.value().unwrap(), "1.1.1.1,2.2.2.2,3.3.3.3");
+        assert_eq!(*heroku.writes.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn give_up_when_the_value_keeps_changing() {
+        let heroku = MockHeroku::with_value("1.1.1.1");
+        let locks = ConfigVarLocks::default();
+
+        for attempt in 0..MAX_CONFIG_VAR_ATTEMPTS {
+            heroku.interfere_after_read(attempt * 2 + 1, &format!("2.2.2.{}", attempt));
+        }
+
+        assert!(update_config_var(&heroku, &locks, "my_app", KEY, add_ip("3.3.3.3")).is_err());
+        assert_eq!(*heroku.writes.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn concurrent_updates_keep_every_change() {
+        let heroku = Arc::new(MockHeroku {
+            read_delay: Some(Duration::from_millis(20)),
+            ..MockHeroku::default()
+        });
+        let locks = Arc::new(ConfigVarLocks::default());
+
+        let threads: Vec<_> = ["1.1.1.1", "2.2.2.2", "3.3.3.3"]
+            .iter()
+            .map(|&ip| {
+                let heroku = Arc::clone(&heroku);
+                let locks = Arc::clone(&locks);
+
+                thread::spawn(move || {
+                    update_config_var(&*heroku, &locks, "my_app", KEY, add_ip(ip)).unwrap();
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut ips: Vec<String> = heroku
+            .value()
+            .unwrap()
+            .split(',')
+            .map(String::from)
+            .collect();
+        ips.sort();
+
+        assert_eq!(ips, vec!["1.1.1.1", "2.2.2.2", "3.3.3.3"]);
+    }
+}
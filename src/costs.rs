@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::utilities::parse_app_settings;
+
+// Parses the monthly price of each dyno size, like the
+// "standard-1X=25;standard-2X=50" in the DYNO_PRICES environmental variable
+pub fn parse_dyno_prices(value: &str) -> Result<HashMap<String, f64>, String> {
+    parse_app_settings(value)?
+        .into_iter()
+        .map(|(size, price)| {
+            let price = price
+                .trim_start_matches('$')
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid price for {}: {}", size, price))?;
+
+            // Heroku isn't consistent about the case of dyno sizes
+            Ok((size.to_lowercase(), price))
+        })
+        .collect()
+}
+
+// The monthly cost of running a number of dynos of a size,
+// if there is a price for that size
+pub fn formation_cost(prices: &HashMap<String, f64>, quantity: i32, size: &str) -> Option<f64> {
+    prices
+        .get(&size.to_lowercase())
+        .map(|price| price * f64::from(quantity))
+}
+
+pub fn format_cost(cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("${:.2}/month", cost),
+        None => String::from("unknown"),
+    }
+}
+
+// Describes how a change affects the monthly cost, like "$50.00/month -> $100.00/month (+$50.00)"
+pub fn cost_change(before: Option<f64>, after: Option<f64>) -> String {
+    let difference = match (before, after) {
+        (Some(before), Some(after)) if after >= before => format!(" (+${:.2})", after - before),
+        (Some(before), Some(after)) => format!(" (-${:.2})", before - after),
+        _ => String::new(),
+    };
+
+    format!(
+        "{} -> {}{}",
+        format_cost(before),
+        format_cost(after),
+        difference
+    )
+}
+
+// Adds up costs, which are unknown when any of them is
+pub fn total_cost(costs: impl IntoIterator<Item = Option<f64>>) -> Option<f64> {
+    costs.into_iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices() -> HashMap<String, f64> {
+        parse_dyno_prices("free=0; Standard-1X=25; standard-2X=$50").unwrap()
+    }
+
+    #[test]
+    fn parse_price_table() {
+        let prices = prices();
+
+        assert_eq!(prices.len(), 3);
+        assert_eq!(prices["standard-1x"], 25.0);
+        assert_eq!(prices["standard-2x"], 50.0);
+        assert!(parse_dyno_prices("standard-1X=cheap").is_err());
+    }
+
+    #[test]
+    fn cost_of_formations() {
+        let prices = prices();
+
+        assert_eq!(formation_cost(&prices, 3, "standard-1X"), Some(75.0));
+        assert_eq!(formation_cost(&prices, 2, "STANDARD-2X"), Some(100.0));
+        assert_eq!(formation_cost(&prices, 1, "performance-l"), None);
+        assert_eq!(format_cost(Some(75.0)), "$75.00/month");
+        assert_eq!(format_cost(None), "unknown");
+    }
+
+    #[test]
+    fn describe_cost_changes() {
+        assert_eq!(
+            cost_change(Some(50.0), Some(100.0)),
+            "$50.00/month -> $100.00/month (+$50.00)"
+        );
+        assert_eq!(
+            cost_change(Some(100.0), Some(25.0)),
+            "$100.00/month -> $25.00/month (-$75.00)"
+        );
+        assert_eq!(cost_change(Some(50.0), None), "$50.00/month -> unknown");
+    }
+
+    #[test]
+    fn add_up_costs() {
+        assert_eq!(total_cost(vec![Some(25.0), Some(50.0)]), Some(75.0));
+        assert_eq!(total_cost(vec![Some(25.0), None]), None);
+        assert_eq!(total_cost(Vec::new()), Some(0.0));
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+
+use crate::store::State;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+// A recurring period (in UTC) during which nothing can be deployed.
+// Windows are either weekly ("Fri 16:00-Mon 09:00") or daily ("22:00-06:00")
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreezeWindow {
+    pub label: String,
+    // Minutes since Monday 00:00, a window wraps around the
+    // end of the week when it ends before it starts
+    spans: Vec<(u32, u32)>,
+}
+
+impl FreezeWindow {
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let minute = minute_of_week(time);
+
+        self.spans.iter().any(|&(start, end)| {
+            if start <= end {
+                minute >= start && minute < end
+            } else {
+                minute >= start || minute < end
+            }
+        })
+    }
+}
+
+// Parses a comma separated list of freeze windows, like the
+// one in the DEPLOY_FREEZE_WINDOWS environmental variable
+pub fn parse_freeze_windows(value: &str) -> Result<Vec<FreezeWindow>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|window| !window.is_empty())
+        .map(parse_freeze_window)
+        .collect()
+}
+
+fn parse_freeze_window(window: &str) -> Result<FreezeWindow, String> {
+    let invalid = || format!("Invalid freeze window: {}", window);
+
+    let mut ends = window.splitn(2, '-');
+    let start = ends.next().ok_or_else(invalid)?.trim();
+    let end = ends.next().ok_or_else(invalid)?.trim();
+
+    let start_parts: Vec<&str> = start.split_whitespace().collect();
+    let end_parts: Vec<&str> = end.split_whitespace().collect();
+
+    let spans = match (start_parts.as_slice(), end_parts.as_slice()) {
+        ([start_day, start_time], [end_day, end_time]) => {
+            let start = day_offset(start_day).ok_or_else(invalid)?
+                + time_of_day(start_time).ok_or_else(invalid)?;
+            let end = day_offset(end_day).ok_or_else(invalid)?
+                + time_of_day(end_time).ok_or_else(invalid)?;
+
+            vec![(start, end)]
+        }
+        ([start_time], [end_time]) => {
+            let start = time_of_day(start_time).ok_or_else(invalid)?;
+            let end = time_of_day(end_time).ok_or_else(invalid)?;
+
+            (0..7)
+                .map(|day| {
+                    let day_start = day * MINUTES_PER_DAY;
+                    (
+                        day_start + start,
+                        (day_start + end + if end < start { MINUTES_PER_DAY } else { 0 })
+                            % MINUTES_PER_WEEK,
+                    )
+                })
+                .collect()
+        }
+        _ => return Err(invalid()),
+    };
+
+    Ok(FreezeWindow {
+        label: window.to_string(),
+        spans,
+    })
+}
+
+fn day_offset(day: &str) -> Option<u32> {
+    let weekday = day.parse::<Weekday>().ok()?;
+    Some(weekday.num_days_from_monday() * MINUTES_PER_DAY)
+}
+
+fn time_of_day(time: &str) -> Option<u32> {
+    let mut parts = time.splitn(2, ':');
+    let hours = parts.next()?.parse::<u32>().ok()?;
+    let minutes = parts.next()?.parse::<u32>().ok()?;
+
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    Some(hours * 60 + minutes)
+}
+
+fn minute_of_week(time: DateTime<Utc>) -> u32 {
+    time.weekday().num_days_from_monday() * MINUTES_PER_DAY + time.hour() * 60 + time.minute()
+}
+
+// Returns why deploys to an app are currently not allowed, if they aren't
+pub fn deploy_blocker(
+    state: &State,
+    freeze_windows: &[FreezeWindow],
+    app_name: &str,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    if let Some(lock) = state.deploy_locks.get(app_name) {
+        return Some(format!(
+            "Deploys to {} were locked by {} at {}: {}",
+            app_name,
+            lock.owner,
+            lock.locked_at.format("%Y-%m-%d %H:%M UTC"),
+            lock.reason
+        ));
+    }
+
+    freeze_windows
+        .iter()
+        .find(|window| window.contains(now))
+        .map(|window| {
+            format!(
+                "Deploys are frozen during the freeze window {} (UTC)",
+                window.label
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::DeployLock;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn weekly_window_wraps_around_the_weekend() {
+        let windows = parse_freeze_windows("Fri 16:00-Mon 09:00").unwrap();
+
+        // 2020-05-01 is a Friday
+        assert!(!windows[0].contains(at("2020-05-01T15:59:00Z")));
+        assert!(windows[0].contains(at("2020-05-01T16:00:00Z")));
+        assert!(windows[0].contains(at("2020-05-03T12:00:00Z")));
+        assert!(windows[0].contains(at("2020-05-04T08:59:00Z")));
+        assert!(!windows[0].contains(at("2020-05-04T09:00:00Z")));
+    }
+
+    #[test]
+    fn daily_window_crosses_midnight() {
+        let windows = parse_freeze_windows("22:00-06:00").unwrap();
+
+        assert!(windows[0].contains(at("2020-05-03T23:30:00Z")));
+        assert!(windows[0].contains(at("2020-05-04T05:00:00Z")));
+        assert!(!windows[0].contains(at("2020-05-04T12:00:00Z")));
+    }
+
+    #[test]
+    fn parse_multiple_windows() {
+        let windows = parse_freeze_windows("Fri 16:00-Mon 09:00, 22:00-23:00").unwrap();
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[1].label, "22:00-23:00");
+        assert!(parse_freeze_windows("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_windows() {
+        assert!(parse_freeze_windows("Fri 16:00").is_err());
+        assert!(parse_freeze_windows("Someday 16:00-Mon 09:00").is_err());
+        assert!(parse_freeze_windows("25:00-26:00").is_err());
+    }
+
+    #[test]
+    fn locks_are_reported_before_freeze_windows() {
+        let mut state = State::default();
+        let windows = parse_freeze_windows("00:00-23:59").unwrap();
+        let now = at("2020-05-04T12:00:00Z");
+
+        assert!(deploy_blocker(&state, &windows, "my_app", now)
+            .unwrap()
+            .contains("freeze window"));
+
+        state.deploy_locks.insert(
+            "my_app".to_string(),
+            DeployLock {
+                owner: "someone#1234".to_string(),
+                reason: "incident".to_string(),
+                locked_at: now,
+            },
+        );
+
+        let blocker = deploy_blocker(&state, &windows, "my_app", now).unwrap();
+        assert!(blocker.contains("someone#1234"));
+        assert!(blocker.contains("incident"));
+        assert!(deploy_blocker(&state, &[], "other_app", now).is_none());
+    }
+}
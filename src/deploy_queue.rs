@@ -0,0 +1,276 @@
+use chrono::{DateTime, Utc};
+use serenity::prelude::TypeMapKey;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+
+// Serializes deploys per app. The request at the front of an app's
+// queue is the one currently deploying, everything behind it waits
+#[derive(Debug, Default)]
+pub struct DeployQueue {
+    queues: Mutex<HashMap<String, VecDeque<QueuedDeploy>>>,
+    changed: Condvar,
+    next_id: Mutex<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedDeploy {
+    pub id: u64,
+    pub git_ref: String,
+    pub requested_by: String,
+    pub requested_at: DateTime<Utc>,
+    pub build_id: Option<String>,
+    pub cancelled_by: Option<String>,
+}
+
+// A place in an app's deploy queue, which is given up when dropped
+#[derive(Debug)]
+pub struct DeployTicket {
+    queue: Arc<DeployQueue>,
+    pub app_name: String,
+    pub id: u64,
+    // Number of deploys ahead of this one when it was queued
+    pub position: usize,
+}
+
+impl DeployQueue {
+    pub fn enqueue(self: &Arc<Self>, app_name: &str, git_ref: &str, user: &str) -> DeployTicket {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(app_name.to_string()).or_default();
+
+        queue.push_back(QueuedDeploy {
+            id,
+            git_ref: git_ref.to_string(),
+            requested_by: user.to_string(),
+            requested_at: Utc::now(),
+            build_id: None,
+            cancelled_by: None,
+        });
+
+        DeployTicket {
+            queue: Arc::clone(self),
+            app_name: app_name.to_string(),
+            id,
+            position: queue.len() - 1,
+        }
+    }
+
+    // Blocks until the deploy is at the front of the queue. Returns
+    // false if the deploy was cancelled while it was waiting
+    pub fn wait_for_turn(&self, app_name: &str, id: u64) -> bool {
+        let mut queues = self.queues.lock().unwrap();
+
+        loop {
+            let queue = match queues.get(app_name) {
+                Some(queue) => queue,
+                None => return false,
+            };
+
+            match queue.iter().position(|deploy| deploy.id == id) {
+                Some(0) => return true,
+                Some(_) => queues = self.changed.wait(queues).unwrap(),
+                None => return false,
+            }
+        }
+    }
+
+    // Removes a deploy that is still waiting. The deploy at the
+    // front of the queue is already running and can't be cancelled here
+    pub fn cancel(&self, app_name: &str, id: u64) -> Result<QueuedDeploy, String> {
+        let mut queues = self.queues.lock().unwrap();
+
+        let queue = queues
+            .get_mut(app_name)
+            .ok_or_else(|| format!("There are no queued deploys for {}", app_name))?;
+
+        match queue.iter().position(|deploy| deploy.id == id) {
+            Some(0) => Err(format!(
+                "Deploy {} for {} is already running and can't be removed from the queue",
+                id, app_name
+            )),
+            Some(index) => {
+                let deploy = queue.remove(index).unwrap();
+                self.changed.notify_all();
+                Ok(deploy)
+            }
+            None => Err(format!("Deploy {} is not queued for {}", id, app_name)),
+        }
+    }
+
+    pub fn list(&self, app_name: &str) -> Vec<QueuedDeploy> {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(app_name)
+            .map(|queue| queue.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // The deploy currently running for an app, if there is one
+    pub fn running(&self, app_name: &str) -> Option<QueuedDeploy> {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(app_name)
+            .and_then(|queue| queue.front().cloned())
+    }
+
+    // Records the Heroku build a running deploy is monitoring
+    pub fn start_build(&self, app_name: &str, id: u64, build_id: &str) {
+        self.update_deploy(app_name, id, |deploy| {
+            deploy.build_id = Some(build_id.to_string())
+        });
+    }
+
+    // Tells the deploy monitoring a build that it was cancelled. Returns
+    // false when no deploy from this bot is monitoring the build
+    pub fn cancel_build(&self, app_name: &str, build_id: &str, user: &str) -> bool {
+        let mut queues = self.queues.lock().unwrap();
+
+        let deploy = queues.get_mut(app_name).and_then(|queue| {
+            queue
+                .iter_mut()
+                .find(|deploy| deploy.build_id.as_deref() == Some(build_id))
+        });
+
+        match deploy {
+            Some(deploy) => {
+                deploy.cancelled_by = Some(user.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn build_cancelled_by(&self, app_name: &str, id: u64) -> Option<String> {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(app_name)
+            .and_then(|queue| queue.iter().find(|deploy| deploy.id == id))
+            .and_then(|deploy| deploy.cancelled_by.clone())
+    }
+
+    fn update_deploy(&self, app_name: &str, id: u64, f: impl FnOnce(&mut QueuedDeploy)) {
+        let mut queues = self.queues.lock().unwrap();
+
+        if let Some(deploy) = queues
+            .get_mut(app_name)
+            .and_then(|queue| queue.iter_mut().find(|deploy| deploy.id == id))
+        {
+            f(deploy);
+        }
+    }
+
+    fn remove(&self, app_name: &str, id: u64) {
+        let mut queues = self.queues.lock().unwrap();
+
+        if let Some(queue) = queues.get_mut(app_name) {
+            queue.retain(|deploy| deploy.id != id);
+
+            if queue.is_empty() {
+                queues.remove(app_name);
+            }
+        }
+
+        self.changed.notify_all();
+    }
+}
+
+impl DeployTicket {
+    pub fn wait_for_turn(&self) -> bool {
+        self.queue.wait_for_turn(&self.app_name, self.id)
+    }
+
+    pub fn start_build(&self, build_id: &str) {
+        self.queue.start_build(&self.app_name, self.id, build_id)
+    }
+
+    pub fn build_cancelled_by(&self) -> Option<String> {
+        self.queue.build_cancelled_by(&self.app_name, self.id)
+    }
+}
+
+impl Drop for DeployTicket {
+    fn drop(&mut self) {
+        self.queue.remove(&self.app_name, self.id);
+    }
+}
+
+impl TypeMapKey for DeployQueue {
+    type Value = Arc<DeployQueue>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn deploys_run_one_at_a_time() {
+        let queue = Arc::new(DeployQueue::default());
+
+        let first = queue.enqueue("my_app", "master", "someone");
+        let second = queue.enqueue("my_app", "abc123", "someone-else");
+        let other_app = queue.enqueue("other_app", "master", "someone");
+
+        assert_eq!(first.position, 0);
+        assert_eq!(second.position, 1);
+        assert_eq!(other_app.position, 0);
+        assert!(first.wait_for_turn());
+        assert_eq!(queue.running("my_app").unwrap().id, first.id);
+
+        let (sender, receiver) = mpsc::channel();
+        let waiter = thread::spawn(move || {
+            sender.send(second.wait_for_turn()).unwrap();
+            second
+        });
+
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+
+        drop(first);
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+        let second = waiter.join().unwrap();
+        assert_eq!(queue.running("my_app").unwrap().id, second.id);
+
+        drop(second);
+        assert!(queue.running("my_app").is_none());
+    }
+
+    #[test]
+    fn cancel_a_waiting_deploy() {
+        let queue = Arc::new(DeployQueue::default());
+
+        let first = queue.enqueue("my_app", "master", "someone");
+        let second = queue.enqueue("my_app", "abc123", "someone-else");
+
+        assert!(queue.cancel("my_app", first.id).is_err());
+        assert_eq!(queue.cancel("my_app", second.id).unwrap().git_ref, "abc123");
+        assert!(!second.wait_for_turn());
+        assert_eq!(queue.list("my_app").len(), 1);
+    }
+
+    #[test]
+    fn cancel_a_running_build() {
+        let queue = Arc::new(DeployQueue::default());
+
+        let ticket = queue.enqueue("my_app", "master", "someone");
+        assert!(!queue.cancel_build("my_app", "build-1", "someone-else"));
+
+        ticket.start_build("build-1");
+        assert!(ticket.build_cancelled_by().is_none());
+
+        assert!(!queue.cancel_build("other_app", "build-1", "someone-else"));
+        assert!(queue.cancel_build("my_app", "build-1", "someone-else"));
+        assert_eq!(ticket.build_cancelled_by().unwrap(), "someone-else");
+    }
+}
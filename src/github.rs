@@ -0,0 +1,561 @@
+use reqwest::blocking::Client as reqwest_client;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::redirect::Policy;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use sha2::{Digest, Sha256};
+
+use std::error::Error;
+use std::io::{self, Read};
+
+use crate::config::Config;
+
+#[derive(Debug, Deserialize)]
+struct CommitResponse {
+    sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestResponse {
+    merged: bool,
+    merge_commit_sha: Option<String>,
+    head: CommitResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitRefResponse {
+    object: GitObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitTagResponse {
+    object: GitObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitObject {
+    r#type: String,
+    sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompareResponse {
+    commits: Vec<CompareCommit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompareCommit {
+    commit: CommitDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitDetails {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CombinedStatusResponse {
+    statuses: Vec<CommitStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitStatus {
+    pub context: String,
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckRunsResponse {
+    total_count: usize,
+    check_runs: Vec<CheckRun>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckRun {
+    pub name: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct GitHubClient {
+    client: reqwest_client,
+    headers: HeaderMap,
+}
+
+impl GitHubClient {
+    pub fn new(auth_token: String) -> Self {
+        let github_client = reqwest_client::new();
+
+        let mut headers = HeaderMap::new();
+        let accept = HeaderValue::from_str("application/vnd.github.v3+json");
+        headers.insert(header::ACCEPT, accept.unwrap());
+
+        let auth = HeaderValue::from_str(&format!("token {}", auth_token));
+        headers.insert(header::AUTHORIZATION, auth.unwrap());
+
+        // Required for the GitHub API
+        // https://developer.github.com/v3/#user-agent-required
+        let useragent = HeaderValue::from_str("rust-lang/crates-io-ops-bot");
+        headers.insert(header::USER_AGENT, useragent.unwrap());
+
+        GitHubClient {
+            client: github_client,
+            headers,
+        }
+    }
+
+    fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, Box<dyn Error>> {
+        let github_response = self
+            .client
+            .get(url)
+            .headers(self.headers.clone())
+            .send()
+            .and_then(|res| res.error_for_status())?;
+
+        Ok(github_response.json()?)
+    }
+
+    // Resolves a branch name, partial sha, or full sha to a full sha
+    pub fn commit_sha(&self, config: &Config, git_ref: &str) -> Result<String, Box<dyn Error>> {
+        let commit: CommitResponse =
+            self.get_json(&repo_url(config, &format!("commits/{}", git_ref)))?;
+
+        Ok(commit.sha)
+    }
+
+    // Resolves a deploy reference that points into the GitHub repo to a full sha
+    pub fn resolve_sha(
+        &self,
+        config: &Config,
+        deploy_ref: &DeployRef,
+    ) -> Result<String, Box<dyn Error>> {
+        match deploy_ref {
+            DeployRef::Git(git_ref) => self.commit_sha(config, git_ref),
+            DeployRef::PullRequest(number) => {
+                let pull_request: PullRequestResponse =
+                    self.get_json(&repo_url(config, &format!("pulls/{}", number)))?;
+
+                // Merged pull requests are deployed as they landed,
+                // open ones as they currently are
+                match pull_request.merge_commit_sha {
+                    Some(merge_commit_sha) if pull_request.merged => Ok(merge_commit_sha),
+                    _ => Ok(pull_request.head.sha),
+                }
+            }
+            DeployRef::Tag(tag) => {
+                let tag_ref: GitRefResponse =
+                    self.get_json(&repo_url(config, &format!("git/ref/tags/{}", tag)))?;
+
+                // Annotated tags point to a tag object rather than the commit itself
+                if tag_ref.object.r#type == "tag" {
+                    let tag_object: GitTagResponse = self.get_json(&repo_url(
+                        config,
+                        &format!("git/tags/{}", tag_ref.object.sha),
+                    ))?;
+
+                    Ok(tag_object.object.sha)
+                } else {
+                    Ok(tag_ref.object.sha)
+                }
+            }
+            DeployRef::Release(version) => Err(format!(
+                "Release v{} is a Heroku release, not a GitHub reference",
+                version
+            )
+            .into()),
+        }
+    }
+
+    // GitHub answers a tarball request with a redirect to a download link that
+    // carries a short lived token, which lets Heroku fetch private repositories
+    pub fn tarball_url(&self, config: &Config, git_sha: &str) -> Result<String, Box<dyn Error>> {
+        let no_redirect_client = reqwest_client::builder().redirect(Policy::none()).build()?;
+
+        let github_response = no_redirect_client
+            .get(&repo_url(config, &format!("tarball/{}", git_sha)))
+            .headers(self.headers.clone())
+            .send()
+            .and_then(|res| res.error_for_status())?;
+
+        let location = github_response
+            .headers()
+            .get(header::LOCATION)
+            .ok_or("GitHub did not return a tarball download link")?;
+
+        Ok(location.to_str()?.to_string())
+    }
+
+    // The checksum Heroku uses to verify the source it downloads for a build
+    pub fn tarball_checksum(&self, tarball_url: &str) -> Result<String, Box<dyn Error>> {
+        // reqwest errors include the URL, which carries the download
+        // token, so they are replaced before reaching the channel
+        let tarball = self
+            .client
+            .get(tarball_url)
+            .send()
+            .and_then(|res| res.error_for_status())
+            .map_err(|error| match error.status() {
+                Some(status) => format!("Downloading the tarball failed with status {}", status),
+                None => String::from("Downloading the tarball failed"),
+            })?;
+
+        Ok(sha256_checksum(tarball).map_err(|_| "Reading the tarball failed")?)
+    }
+
+    // The messages of the commits between two commits, oldest first
+    pub fn compare_commit_messages(
+        &self,
+        config: &Config,
+        base_sha: &str,
+        head_sha: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let comparison: CompareResponse = self.get_json(&repo_url(
+            config,
+            &format!("compare/{}...{}", base_sha, head_sha),
+        ))?;
+
+        Ok(comparison
+            .commits
+            .into_iter()
+            .map(|commit| commit.commit.message)
+            .collect())
+    }
+
+    // Combines the commit statuses and the check runs reported for a commit
+    pub fn ci_status(&self, config: &Config, git_sha: &str) -> Result<CiStatus, Box<dyn Error>> {
+        let combined: CombinedStatusResponse =
+            self.get_json(&repo_url(config, &format!("commits/{}/status", git_sha)))?;
+
+        // Check runs come in pages of at most 100
+        let mut check_runs = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let response: CheckRunsResponse = self.get_json(&repo_url(
+                config,
+                &format!("commits/{}/check-runs?per_page=100&page={}", git_sha, page),
+            ))?;
+
+            let page_is_empty = response.check_runs.is_empty();
+            check_runs.extend(response.check_runs);
+
+            if page_is_empty || check_runs.len() >= response.total_count {
+                break;
+            }
+
+            page += 1;
+        }
+
+        Ok(summarize_ci_status(&combined.statuses, &check_runs))
+    }
+}
+
+// Something deploy_app can deploy: a branch name, partial sha or full sha,
+// a pull request ("pr:1234"), a tag ("tag:v2024.1"), or the commit an
+// earlier Heroku release of the app was built from ("release:v123")
+#[derive(Debug, PartialEq)]
+pub enum DeployRef {
+    Git(String),
+    PullRequest(u64),
+    Tag(String),
+    Release(u64),
+}
+
+pub fn parse_deploy_ref(value: &str) -> Result<DeployRef, String> {
+    if let Some(number) = value.strip_prefix("pr:") {
+        return number
+            .trim_start_matches('#')
+            .parse()
+            .map(DeployRef::PullRequest)
+            .map_err(|_| format!("Invalid pull request number: {}", number));
+    }
+
+    if let Some(tag) = value.strip_prefix("tag:") {
+        if tag.is_empty() {
+            return Err(String::from("You must include a tag name after tag:"));
+        }
+
+        return Ok(DeployRef::Tag(tag.to_string()));
+    }
+
+    if let Some(version) = value.strip_prefix("release:") {
+        return version
+            .trim_start_matches('v')
+            .parse()
+            .map(DeployRef::Release)
+            .map_err(|_| format!("Invalid release version: {}", version));
+    }
+
+    Ok(DeployRef::Git(value.to_string()))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CiState {
+    Passing,
+    Pending,
+    Failing,
+}
+
+#[derive(Debug)]
+pub struct CiStatus {
+    pub state: CiState,
+    pub total: usize,
+    pub pending: Vec<String>,
+    pub failing: Vec<String>,
+}
+
+impl CiStatus {
+    pub fn summary(&self) -> String {
+        match self.state {
+            CiState::Passing if self.total == 0 => String::from("no CI checks were reported"),
+            CiState::Passing => format!("all {} CI checks passed", self.total),
+            CiState::Pending => format!("CI checks still pending: {}", self.pending.join(", ")),
+            CiState::Failing => format!("CI checks failing: {}", self.failing.join(", ")),
+        }
+    }
+}
+
+// A commit is failing if any check failed, pending if any check has not
+// finished yet, and passing otherwise (including when no checks ran)
+pub fn summarize_ci_status(statuses: &[CommitStatus], check_runs: &[CheckRun]) -> CiStatus {
+    let mut pending = Vec::new();
+    let mut failing = Vec::new();
+
+    for status in statuses {
+        match status.state.as_str() {
+            "success" => {}
+            "pending" => pending.push(status.context.clone()),
+            _ => failing.push(status.context.clone()),
+        }
+    }
+
+    for check_run in check_runs {
+        if check_run.status != "completed" {
+            pending.push(check_run.name.clone());
+            continue;
+        }
+
+        match check_run.conclusion.as_deref() {
+            Some("success") | Some("neutral") | Some("skipped") => {}
+            _ => failing.push(check_run.name.clone()),
+        }
+    }
+
+    let state = if !failing.is_empty() {
+        CiState::Failing
+    } else if !pending.is_empty() {
+        CiState::Pending
+    } else {
+        CiState::Passing
+    };
+
+    CiStatus {
+        state,
+        total: statuses.len() + check_runs.len(),
+        pending,
+        failing,
+    }
+}
+
+pub fn sha256_checksum(mut source: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut source, &mut hasher)?;
+
+    Ok(format!("SHA256:{:x}", hasher.finalize()))
+}
+
+// Finds the pull request a commit merged, from merge commit messages
+// ("Merge pull request #123 from ..."), bors merges ("Auto merge of #123 - ...")
+// and squash merges ("Title (#123)"). Returns the pull request number and title
+pub fn pull_request_from_commit(message: &str) -> Option<(u64, String)> {
+    let mut lines = message.lines();
+    let first_line = lines.next()?.trim();
+
+    let merged_number = first_line
+        .strip_prefix("Merge pull request #")
+        .or_else(|| first_line.strip_prefix("Auto merge of #"));
+
+    if let Some(rest) = merged_number {
+        let number = rest
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()?;
+
+        let title = lines
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("")
+            .to_string();
+
+        return Some((number, title));
+    }
+
+    let rest = first_line.strip_suffix(')')?;
+    let start = rest.rfind("(#")?;
+    let number = rest[start + 2..].parse().ok()?;
+
+    Some((number, rest[..start].trim().to_string()))
+}
+
+pub fn pull_request_url(config: &Config, number: u64) -> String {
+    format!(
+        "https://github.com/{}/{}/pull/{}",
+        config.github_org, config.github_repo, number
+    )
+}
+
+pub fn compare_url(config: &Config, base_sha: &str, head_sha: &str) -> String {
+    format!(
+        "https://github.com/{}/{}/compare/{}...{}",
+        config.github_org, config.github_repo, base_sha, head_sha
+    )
+}
+
+// The GitHub page for a commit of the deployed repo
+pub fn commit_url(config: &Config, git_sha: &str) -> String {
+    format!(
+        "https://github.com/{}/{}/commit/{}",
+        config.github_org, config.github_repo, git_sha
+    )
+}
+
+fn repo_url(config: &Config, path: &str) -> String {
+    format!(
+        "https://api.github.com/repos/{}/{}/{}",
+        config.github_org, config.github_repo, path
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(context: &str, state: &str) -> CommitStatus {
+        CommitStatus {
+            context: context.to_string(),
+            state: state.to_string(),
+        }
+    }
+
+    fn check_run(name: &str, status: &str, conclusion: Option<&str>) -> CheckRun {
+        CheckRun {
+            name: name.to_string(),
+            status: status.to_string(),
+            conclusion: conclusion.map(String::from),
+        }
+    }
+
+    #[test]
+    fn passing_when_all_checks_succeed() {
+        let ci_status = summarize_ci_status(
+            &[status("ci/travis", "success")],
+            &[
+                check_run("test", "completed", Some("success")),
+                check_run("lint", "completed", Some("skipped")),
+            ],
+        );
+
+        assert_eq!(ci_status.state, CiState::Passing);
+        assert_eq!(ci_status.total, 3);
+    }
+
+    #[test]
+    fn passing_when_no_checks_ran() {
+        let ci_status = summarize_ci_status(&[], &[]);
+
+        assert_eq!(ci_status.state, CiState::Passing);
+        assert_eq!(ci_status.summary(), "no CI checks were reported");
+    }
+
+    #[test]
+    fn pending_when_a_check_has_not_finished() {
+        let ci_status = summarize_ci_status(
+            &[status("ci/travis", "success")],
+            &[check_run("test", "in_progress", None)],
+        );
+
+        assert_eq!(ci_status.state, CiState::Pending);
+        assert_eq!(ci_status.pending, vec!["test"]);
+    }
+
+    #[test]
+    fn failing_takes_precedence_over_pending() {
+        let ci_status = summarize_ci_status(
+            &[status("ci/travis", "pending"), status("deploy", "error")],
+            &[check_run("test", "completed", Some("timed_out"))],
+        );
+
+        assert_eq!(ci_status.state, CiState::Failing);
+        assert_eq!(ci_status.failing, vec!["deploy", "test"]);
+        assert_eq!(ci_status.pending, vec!["ci/travis"]);
+    }
+
+    #[test]
+    fn parse_deploy_refs() {
+        assert_eq!(
+            parse_deploy_ref("master").unwrap(),
+            DeployRef::Git("master".to_string())
+        );
+        assert_eq!(
+            parse_deploy_ref("pr:1234").unwrap(),
+            DeployRef::PullRequest(1234)
+        );
+        assert_eq!(
+            parse_deploy_ref("pr:#1234").unwrap(),
+            DeployRef::PullRequest(1234)
+        );
+        assert_eq!(
+            parse_deploy_ref("tag:v2024.1").unwrap(),
+            DeployRef::Tag("v2024.1".to_string())
+        );
+        assert_eq!(
+            parse_deploy_ref("release:v123").unwrap(),
+            DeployRef::Release(123)
+        );
+        assert_eq!(
+            parse_deploy_ref("release:123").unwrap(),
+            DeployRef::Release(123)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_deploy_refs() {
+        assert!(parse_deploy_ref("pr:abc").is_err());
+        assert!(parse_deploy_ref("tag:").is_err());
+        assert!(parse_deploy_ref("release:latest").is_err());
+    }
+
+    #[test]
+    fn checksum_in_heroku_format() {
+        assert_eq!(
+            sha256_checksum("abc".as_bytes()).unwrap(),
+            "SHA256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn pull_requests_from_commit_messages() {
+        assert_eq!(
+            pull_request_from_commit(
+                "Merge pull request #123 from someone/branch\n\nFix the thing"
+            ),
+            Some((123, "Fix the thing".to_string()))
+        );
+        assert_eq!(
+            pull_request_from_commit(
+                "Auto merge of #2456 - someone:branch, r=reviewer\n\nAdd the thing\n\nLonger body"
+            ),
+            Some((2456, "Add the thing".to_string()))
+        );
+        assert_eq!(
+            pull_request_from_commit("Bump serde (#789)\n\nBody"),
+            Some((789, "Bump serde".to_string()))
+        );
+        assert_eq!(pull_request_from_commit("Fix typo in README"), None);
+        assert_eq!(pull_request_from_commit("Fix (#abc)"), None);
+    }
+}
@@ -0,0 +1,452 @@
+use chrono::{DateTime, Utc};
+
+use ipnet::IpNet;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+
+use crate::store::BlockedIp;
+use crate::utilities::relative_time;
+
+// Parses an IP address or CIDR network (IPv4 or IPv6) to block. Ranges with
+// host bits set are refused, so a typo like 123.4.5.67/24 can't quietly
+// turn into a block of 256 addresses
+pub fn parse_ip_block(input: &str) -> Result<IpNet, String> {
+    let input = input.trim();
+
+    if let Ok(ip) = input.parse::<IpAddr>() {
+        return Ok(IpNet::from(ip));
+    }
+
+    let net = input.parse::<IpNet>().map_err(|_| {
+        format!(
+            "{} is not a valid IP address or CIDR range (like 123.4.5.67, 123.4.5.0/24 or 2001:db8::/32)",
+            input
+        )
+    })?;
+
+    if net.trunc() != net {
+        return Err(format!(
+            "{} has host bits set, which would block all of {}. Did you mean {} or {}?",
+            input,
+            net.trunc(),
+            net.addr(),
+            net.trunc()
+        ));
+    }
+
+    Ok(net)
+}
+
+// Parses an entry that is already in BLOCKED_IPS. Entries added outside the
+// bot may have host bits set, they are read as the whole range they cover
+pub fn parse_blocked_entry(entry: &str) -> Result<IpNet, String> {
+    match entry.trim().parse::<IpNet>() {
+        Ok(net) => Ok(net.trunc()),
+        Err(_) => parse_ip_block(entry),
+    }
+}
+
+// How a block is stored in BLOCKED_IPS: single addresses without a prefix
+// length, ranges with their host bits cleared, IPv6 in its compressed form
+pub fn canonical_ip_block(net: &IpNet) -> String {
+    if net.prefix_len() == net.max_prefix_len() {
+        net.addr().to_string()
+    } else {
+        net.trunc().to_string()
+    }
+}
+
+// The existing blocks that already cover a new block, and the
+// existing blocks the new block would cover
+pub fn block_overlaps(new_block: &IpNet, blocked: &HashSet<String>) -> (Vec<String>, Vec<String>) {
+    let mut covered_by = Vec::new();
+    let mut covers = Vec::new();
+
+    for existing in blocked {
+        let existing_block = match parse_blocked_entry(existing) {
+            Ok(existing_block) => existing_block,
+            Err(_) => continue,
+        };
+
+        if existing_block == *new_block {
+            continue;
+        }
+
+        if existing_block.contains(new_block) {
+            covered_by.push(existing.clone());
+        } else if new_block.contains(&existing_block) {
+            covers.push(existing.clone());
+        }
+    }
+
+    covered_by.sort();
+    covers.sort();
+    (covered_by, covers)
+}
+
+// The entry in BLOCKED_IPS that blocks an address, if any does
+pub fn blocking_entry(ip: &str, blocked: &HashSet<String>) -> Option<String> {
+    let ip = ip.parse::<IpAddr>().ok()?;
+
+    let mut entries: Vec<&String> = blocked
+        .iter()
+        .filter(|entry| matches!(parse_blocked_entry(entry), Ok(block) if block.contains(&ip)))
+        .collect();
+
+    entries.sort();
+    entries.first().map(|entry| entry.to_string())
+}
+
+// The entries in BLOCKED_IPS that are the same block as the input,
+// even if they were stored in a different form
+pub fn matching_entries(block: &IpNet, blocked: &HashSet<String>) -> Vec<String> {
+    blocked
+        .iter()
+        .filter(|entry| matches!(parse_blocked_entry(entry), Ok(existing) if existing == *block))
+        .cloned()
+        .collect()
+}
+
+// Describes each entry in BLOCKED_IPS with who blocked it, when, why and
+// until when, sorted by address
+pub fn blocked_ips_lines(
+    blocked: &HashSet<String>,
+    details: Option<&BTreeMap<String, BlockedIp>>,
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    sorted_entries(blocked)
+        .into_iter()
+        .map(|(block, entry)| {
+            // The bot keeps details under the canonical form of each block
+            let blocked_ip = details.and_then(|details| {
+                details
+                    .get(entry)
+                    .or_else(|| block.and_then(|block| details.get(&canonical_ip_block(&block))))
+            });
+
+            match blocked_ip {
+                Some(blocked_ip) => blocked_ip_line(entry, blocked_ip, now),
+                None => format!(
+                    "`{}` (no details, it wasn't blocked through the bot)",
+                    entry
+                ),
+            }
+        })
+        .collect()
+}
+
+// The entries in BLOCKED_IPS sorted by address, with
+// entries that aren't valid blocks last
+fn sorted_entries(blocked: &HashSet<String>) -> Vec<(Option<IpNet>, &String)> {
+    let mut entries: Vec<(Option<IpNet>, &String)> = blocked
+        .iter()
+        .map(|entry| (parse_blocked_entry(entry).ok(), entry))
+        .collect();
+
+    entries.sort_by(|(a_block, a), (b_block, b)| match (a_block, b_block) {
+        (Some(a_block), Some(b_block)) => a_block.cmp(b_block),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    });
+
+    entries
+}
+
+// BLOCKED_IPS as a file with one entry per line, which block_ips can import again
+pub fn export_blocked_ips(blocked: &HashSet<String>) -> String {
+    let mut export: String = sorted_entries(blocked)
+        .into_iter()
+        .map(|(_, entry)| entry.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    export.push('\n');
+    export
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct BulkBlocks {
+    // Canonical blocks that aren't in BLOCKED_IPS yet
+    pub accepted: Vec<String>,
+    // Entries that aren't valid addresses or ranges
+    pub rejected: Vec<String>,
+    // Entries already in BLOCKED_IPS or listed earlier in the file
+    pub duplicates: Vec<String>,
+    // Accepted blocks already covered by ranges in BLOCKED_IPS or earlier
+    // in the file, with the ranges that cover them
+    pub covered: Vec<(String, Vec<String>)>,
+    // Accepted ranges that cover entries in BLOCKED_IPS or earlier
+    // in the file, with the entries they cover
+    pub covering: Vec<(String, Vec<String>)>,
+}
+
+// The largest file block_ips downloads, far more than fits in BLOCKED_IPS
+pub const MAX_IMPORT_SIZE: u64 = 1024 * 1024;
+
+// Reads a text file of addresses and ranges, one per line, or a CSV file
+// with them in the first column. Blank lines, comments starting with #
+// and a header row are skipped
+pub fn parse_bulk_blocks(text: &str, blocked: &HashSet<String>) -> BulkBlocks {
+    let mut bulk_blocks = BulkBlocks::default();
+    let mut seen: HashSet<IpNet> = HashSet::new();
+    let mut known = blocked.clone();
+
+    let entries = text
+        .lines()
+        .map(|line| {
+            line.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+                .next()
+                .unwrap_or_default()
+                .trim_matches(|c| c == '"' || c == '\'')
+        })
+        .filter(|entry| !entry.is_empty() && !entry.starts_with('#'));
+
+    for (index, entry) in entries.enumerate() {
+        let block = match parse_ip_block(entry) {
+            Ok(block) => block,
+            Err(_) if index == 0 && !entry.contains(|c: char| c.is_ascii_digit()) => continue,
+            Err(_) => {
+                bulk_blocks.rejected.push(entry.to_string());
+                continue;
+            }
+        };
+
+        if !seen.insert(block) || !matching_entries(&block, blocked).is_empty() {
+            bulk_blocks.duplicates.push(entry.to_string());
+        } else {
+            let ip_addr = canonical_ip_block(&block);
+            let (covered_by, covers) = block_overlaps(&block, &known);
+
+            if !covered_by.is_empty() {
+                bulk_blocks.covered.push((ip_addr.clone(), covered_by));
+            }
+
+            if !covers.is_empty() {
+                bulk_blocks.covering.push((ip_addr.clone(), covers));
+            }
+
+            known.insert(ip_addr.clone());
+            bulk_blocks.accepted.push(ip_addr);
+        }
+    }
+
+    bulk_blocks
+}
+
+fn blocked_ip_line(entry: &str, blocked_ip: &BlockedIp, now: DateTime<Utc>) -> String {
+    let mut line = format!(
+        "`{}` blocked by {} {}",
+        entry,
+        blocked_ip.blocked_by,
+        relative_time(blocked_ip.blocked_at, now)
+    );
+
+    if let Some(reason) = &blocked_ip.reason {
+        line.push_str(&format!(": {}", reason));
+    }
+
+    if let Some(expires_at) = blocked_ip.expires_at {
+        line.push_str(&format!(
+            " (expires {})",
+            expires_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked(entries: &[&str]) -> HashSet<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    fn canonical(input: &str) -> String {
+        canonical_ip_block(&parse_ip_block(input).unwrap())
+    }
+
+    #[test]
+    fn parse_addresses_and_ranges() {
+        assert_eq!(canonical("123.4.5.67"), "123.4.5.67");
+        assert_eq!(canonical(" 123.4.5.67/32 "), "123.4.5.67");
+        assert_eq!(canonical("123.4.5.0/24"), "123.4.5.0/24");
+        assert_eq!(canonical("2001:DB8:0:0::1"), "2001:db8::1");
+        assert_eq!(canonical("2001:db8::/32"), "2001:db8::/32");
+    }
+
+    #[test]
+    fn reject_ranges_with_host_bits() {
+        let error = parse_ip_block("123.4.5.67/24").unwrap_err();
+        assert!(error.contains("Did you mean 123.4.5.67 or 123.4.5.0/24?"));
+        assert!(parse_ip_block("2001:db8::1/32").is_err());
+
+        // Existing entries with host bits still count as the range they cover
+        assert_eq!(
+            parse_blocked_entry("123.4.5.67/24").unwrap(),
+            parse_ip_block("123.4.5.0/24").unwrap()
+        );
+        assert_eq!(
+            blocking_entry("123.4.5.1", &blocked(&["123.4.5.67/24"])),
+            Some("123.4.5.67/24".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_invalid_input() {
+        assert!(parse_ip_block("123.4.5.677").is_err());
+        assert!(parse_ip_block("123.4.5").is_err());
+        assert!(parse_ip_block("123.4.5.0/33").is_err());
+        assert!(parse_ip_block("2001:db8::g").is_err());
+        assert!(parse_ip_block("").is_err());
+    }
+
+    #[test]
+    fn find_overlapping_blocks() {
+        let existing = blocked(&["10.0.0.0/8", "192.168.1.5", "192.168.2.0/24", "garbage"]);
+
+        let (covered_by, covers) = block_overlaps(&parse_ip_block("10.1.2.3").unwrap(), &existing);
+        assert_eq!(covered_by, vec!["10.0.0.0/8"]);
+        assert!(covers.is_empty());
+
+        let (covered_by, covers) =
+            block_overlaps(&parse_ip_block("192.168.0.0/16").unwrap(), &existing);
+        assert!(covered_by.is_empty());
+        assert_eq!(covers, vec!["192.168.1.5", "192.168.2.0/24"]);
+
+        let (covered_by, covers) =
+            block_overlaps(&parse_ip_block("2001:db8::1").unwrap(), &existing);
+        assert!(covered_by.is_empty() && covers.is_empty());
+    }
+
+    #[test]
+    fn find_the_entry_blocking_an_address() {
+        let existing = blocked(&["10.0.0.0/8", "2001:db8::/32"]);
+
+        assert_eq!(blocking_entry("10.4.5.6", &existing).unwrap(), "10.0.0.0/8");
+        assert_eq!(
+            blocking_entry("2001:db8::42", &existing).unwrap(),
+            "2001:db8::/32"
+        );
+        assert!(blocking_entry("11.0.0.1", &existing).is_none());
+        assert!(blocking_entry("not an ip", &existing).is_none());
+    }
+
+    #[test]
+    fn match_entries_stored_in_another_form() {
+        let existing = blocked(&["2001:DB8::1", "10.0.0.0/8"]);
+
+        assert_eq!(
+            matching_entries(&parse_ip_block("2001:db8:0::1").unwrap(), &existing),
+            vec!["2001:DB8::1"]
+        );
+        assert!(matching_entries(&parse_ip_block("10.0.0.1").unwrap(), &existing).is_empty());
+    }
+
+    #[test]
+    fn describe_blocked_ips() {
+        let existing = blocked(&["2001:db8::1", "123.45.6.78", "10.0.0.0/8", "garbage"]);
+
+        let mut details = BTreeMap::new();
+        details.insert(
+            "123.45.6.78".to_string(),
+            BlockedIp {
+                blocked_by: "you#1234".to_string(),
+                blocked_at: "2020-05-04T09:00:00Z".parse().unwrap(),
+                expires_at: Some("2020-05-05T09:00:00Z".parse().unwrap()),
+                channel_id: None,
+                reason: Some("scraping every crate page".to_string()),
+            },
+        );
+        details.insert(
+            "2001:db8::1".to_string(),
+            BlockedIp {
+                blocked_by: "someone#5678".to_string(),
+                blocked_at: "2020-05-01T12:00:00Z".parse().unwrap(),
+                expires_at: None,
+                channel_id: None,
+                reason: None,
+            },
+        );
+
+        let now = "2020-05-04T12:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            blocked_ips_lines(&existing, Some(&details), now),
+            vec![
+                "`10.0.0.0/8` (no details, it wasn't blocked through the bot)",
+                "`123.45.6.78` blocked by you#1234 3 hours ago: scraping every crate page \
+                 (expires 2020-05-05 09:00 UTC)",
+                "`2001:db8::1` blocked by someone#5678 3 days ago",
+                "`garbage` (no details, it wasn't blocked through the bot)",
+            ]
+        );
+
+        assert_eq!(blocked_ips_lines(&existing, None, now).len(), 4);
+    }
+
+    #[test]
+    fn parse_a_bulk_block_file() {
+        let existing = blocked(&["10.0.0.0/8", "192.168.1.5"]);
+
+        let text = "\
+ip,reason
+# from the abuse report
+123.45.6.78,scraping
+\"2001:DB8::1\",scraping
+
+10.0.0.0/8
+123.45.6.78
+123.4.5.677
+192.168.1.0/24;spam
+10.1.2.3
+192.168.1.7
+";
+
+        assert_eq!(
+            parse_bulk_blocks(text, &existing),
+            BulkBlocks {
+                accepted: vec![
+                    "123.45.6.78".to_string(),
+                    "2001:db8::1".to_string(),
+                    "192.168.1.0/24".to_string(),
+                    "10.1.2.3".to_string(),
+                    "192.168.1.7".to_string()
+                ],
+                rejected: vec!["123.4.5.677".to_string()],
+                duplicates: vec!["10.0.0.0/8".to_string(), "123.45.6.78".to_string()],
+                covered: vec![
+                    ("10.1.2.3".to_string(), vec!["10.0.0.0/8".to_string()]),
+                    (
+                        "192.168.1.7".to_string(),
+                        vec!["192.168.1.0/24".to_string()]
+                    ),
+                ],
+                covering: vec![(
+                    "192.168.1.0/24".to_string(),
+                    vec!["192.168.1.5".to_string()]
+                )],
+            }
+        );
+
+        let plain = parse_bulk_blocks("1.2.3.4\nnot-an-ip\n", &existing);
+        assert_eq!(plain.accepted, vec!["1.2.3.4"]);
+        assert_eq!(plain.rejected, vec!["not-an-ip"]);
+    }
+
+    #[test]
+    fn export_sorted_entries() {
+        let existing = blocked(&["2001:db8::1", "123.45.6.78", "10.0.0.0/8"]);
+        let export = export_blocked_ips(&existing);
+
+        assert_eq!(export, "10.0.0.0/8\n123.45.6.78\n2001:db8::1\n");
+        assert_eq!(
+            parse_bulk_blocks(&export, &HashSet::new()).accepted.len(),
+            3
+        );
+    }
+}
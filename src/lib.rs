@@ -3,6 +3,8 @@
 
 use heroku_rs::framework::{auth::Credentials, ApiEnvironment, HttpApiClient, HttpApiClientConfig};
 
+use reqwest::header::{HeaderMap, HeaderValue, RANGE};
+
 use serenity::client::Client;
 use serenity::framework::standard::DispatchError::{NotEnoughArguments, TooManyArguments};
 use serenity::framework::standard::{
@@ -15,19 +17,57 @@ use serenity::model::prelude::{Message, UserId};
 use serenity::prelude::{Context, EventHandler, TypeMapKey};
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::thread;
 
 mod commands;
 
-use commands::{heroku::*, math::*, myid::*, ping::*};
+use commands::{
+    deploys::*, dynos::*, heroku::*, ip_blocks::*, log_drain::*, logs::*, math::*, myid::*,
+    ping::*, schedules::*,
+};
 
 mod authorizations;
 
 pub mod config;
+pub mod config_vars;
+
+pub mod costs;
+
+pub mod deploy_locks;
+
+pub mod deploy_queue;
+
+mod github;
+
+mod ip_blocks;
+
+mod log_drain;
+
+mod logs;
+
+mod release_diff;
+
+mod release_targets;
+
+mod scaling;
+
+pub mod schedules;
+
+pub mod store;
 
 pub mod utilities;
 
 use crate::config::Config;
 
+use crate::config_vars::ConfigVarLocks;
+use crate::deploy_queue::DeployQueue;
+
+use crate::log_drain::DrainStats;
+
+use crate::release_targets::{newest_releases_range, MAX_RELEASES};
+
+use crate::store::Store;
+
 use crate::authorizations::users::*;
 
 #[group]
@@ -37,14 +77,36 @@ use crate::authorizations::users::*;
     myid,
     get_app,
     get_apps,
+    cost,
     restart_app,
+    dynos,
+    restart_dyno,
+    stop_dyno,
+    rolling_restart,
+    logs,
     scale_app,
     update_app_config,
     get_app_releases,
+    release_diff,
     rollback_app,
     block_ip,
     unblock_ip,
-    deploy_app
+    blocked_ips,
+    block_ips,
+    export_blocked_ips,
+    top_ips,
+    deploy_app,
+    clear_build_cache,
+    lock_deploys,
+    unlock_deploys,
+    deploy_queue,
+    cancel_deploy,
+    promote,
+    cancel_build,
+    schedule_scale,
+    list_schedules,
+    pause_schedule,
+    resume_schedule
 )]
 struct General;
 
@@ -62,6 +124,13 @@ impl TypeMapKey for HerokuClientKey {
     type Value = Arc<heroku_rs::framework::HttpApiClient>;
 }
 
+// A Heroku client that asks for the newest releases first, see newest_releases_range
+struct ReleasesClientKey;
+
+impl TypeMapKey for ReleasesClientKey {
+    type Value = Arc<heroku_rs::framework::HttpApiClient>;
+}
+
 #[help]
 #[individual_command_tip = "Hello! こんにちは！Hola! Bonjour! Ciao! 您好!\n\
 If you want more information about a specific command, just pass the command as argument."]
@@ -86,10 +155,17 @@ pub fn run(config: Config) {
 
     let heroku_client_instance = initial_heroku_client(&config.heroku_api_key);
 
+    let store = Store::open(&config.state_file).expect("Err opening the state file");
+
     {
         let mut data = client.data.write();
         data.insert::<HerokuClientKey>(Arc::new(heroku_client_instance));
+        data.insert::<ReleasesClientKey>(Arc::new(releases_heroku_client(&config.heroku_api_key)));
         data.insert::<Config>(Arc::new(config.clone()));
+        data.insert::<Store>(Arc::new(store));
+        data.insert::<DeployQueue>(Arc::new(DeployQueue::default()));
+        data.insert::<DrainStats>(Arc::new(DrainStats::default()));
+        data.insert::<ConfigVarLocks>(Arc::new(ConfigVarLocks::default()));
     }
 
     client.with_framework(
@@ -145,7 +221,7 @@ pub fn run(config: Config) {
             .after(|ctx, msg, cmd_name, error| {
                 if let Err(err) = error {
                     msg.reply(
-                        &ctx,
+                        ctx,
                         format!("There was an error when running {}: {:?}", cmd_name, err),
                     )
                     .ok();
@@ -154,7 +230,7 @@ pub fn run(config: Config) {
             .configure(|c| c.prefix("!")) // set the bot's prefix to "!""
             .unrecognised_command(|ctx, msg, unknown_command_name| {
                 msg.reply(
-                    &ctx,
+                    ctx,
                     format!("Could not find a command named `{}`", unknown_command_name),
                 )
                 .ok();
@@ -163,6 +239,24 @@ pub fn run(config: Config) {
             .group(&GENERAL_GROUP),
     );
 
+    {
+        let data = Arc::clone(&client.data);
+        let http = Arc::clone(&client.cache_and_http.http);
+        thread::spawn(move || run_scaling_schedules(data, http));
+    }
+
+    {
+        let data = Arc::clone(&client.data);
+        let http = Arc::clone(&client.cache_and_http.http);
+        thread::spawn(move || run_ip_block_expiry(data, http));
+    }
+
+    if config.log_drain_address.is_some() {
+        let data = Arc::clone(&client.data);
+        let http = Arc::clone(&client.cache_and_http.http);
+        thread::spawn(move || run_log_drain(data, http));
+    }
+
     if let Err(why) = client.start() {
         println!("Client error: {:?}", why);
     }
@@ -182,3 +276,21 @@ fn initial_heroku_client(api_key: &str) -> heroku_rs::framework::HttpApiClient {
     )
     .unwrap()
 }
+
+fn releases_heroku_client(api_key: &str) -> heroku_rs::framework::HttpApiClient {
+    let mut default_headers = HeaderMap::new();
+    default_headers.insert(
+        RANGE,
+        HeaderValue::from_str(&newest_releases_range(MAX_RELEASES)).unwrap(),
+    );
+
+    HttpApiClient::new(
+        heroku_credentials(api_key),
+        HttpApiClientConfig {
+            default_headers,
+            ..HttpApiClientConfig::default()
+        },
+        ApiEnvironment::Production,
+    )
+    .unwrap()
+}
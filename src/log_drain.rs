@@ -0,0 +1,640 @@
+use chrono::{DateTime, Utc};
+use serenity::prelude::TypeMapKey;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// One line from a Heroku log drain. Heroku's own lines (like the router's)
+// come from the "heroku" source and the app's output from the "app" source
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub source: String,
+    pub process: String,
+    pub message: String,
+}
+
+// The most a single drain request may send. Heroku batches drain messages
+// into requests far smaller than this
+pub const MAX_DRAIN_BODY: u64 = 1024 * 1024;
+
+// Compares a drain token without stopping at the first wrong character,
+// so the response time doesn't give away how much of a guess was right
+pub fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// Heroku sends drains as syslog messages with octet counting framing,
+// like "83 <40>1 2012-11-30T06:45:29+00:00 host app web.3 - State changed"
+pub fn parse_drain_body(body: &str) -> Vec<LogLine> {
+    let mut lines = Vec::new();
+    let mut rest = body.trim_start();
+
+    while let Some(space) = rest.find(' ') {
+        let length = match rest[..space].parse::<usize>() {
+            Ok(length) => length,
+            Err(_) => break,
+        };
+
+        let frame = match rest.get(space + 1..space + 1 + length) {
+            Some(frame) => frame,
+            None => break,
+        };
+
+        if let Some(line) = parse_syslog_message(frame) {
+            lines.push(line);
+        }
+
+        rest = rest[space + 1 + length..].trim_start();
+    }
+
+    lines
+}
+
+// Log session lines look like
+// "2020-05-04T12:00:00.123456+00:00 heroku[router]: at=info method=GET ..."
+pub fn parse_log_session_line(line: &str) -> Option<(DateTime<Utc>, LogLine)> {
+    let mut parts = line.splitn(3, ' ');
+    let at = DateTime::parse_from_rfc3339(parts.next()?).ok()?;
+    let origin = parts.next()?.strip_suffix(':')?;
+    let message = parts.next().unwrap_or_default();
+
+    let bracket = origin.find('[')?;
+
+    Some((
+        at.with_timezone(&Utc),
+        LogLine {
+            source: origin[..bracket].to_string(),
+            process: origin[bracket + 1..].trim_end_matches(']').to_string(),
+            message: message.to_string(),
+        },
+    ))
+}
+
+fn parse_syslog_message(frame: &str) -> Option<LogLine> {
+    // <priority>version timestamp hostname source process message_id message
+    let parts: Vec<&str> = frame.trim_end().splitn(7, ' ').collect();
+
+    if parts.len() != 7 || !parts[0].starts_with('<') {
+        return None;
+    }
+
+    Some(LogLine {
+        source: parts[3].to_string(),
+        process: parts[4].to_string(),
+        message: parts[6].to_string(),
+    })
+}
+
+// Splits a logfmt message like `at=error code=H12 desc="Request timeout"`
+pub fn parse_key_values(message: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut rest = message.trim();
+
+    while let Some(equals) = rest.find('=') {
+        let key = rest[..equals].trim();
+        let after = &rest[equals + 1..];
+
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.find(' ') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            }
+        };
+
+        values.insert(key.to_string(), value.to_string());
+        rest = remaining.trim_start();
+    }
+
+    values
+}
+
+// The parts of a router line the bot keeps track of
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouterRequest {
+    pub path: Option<String>,
+    pub status: Option<u16>,
+    pub service_ms: Option<u64>,
+    pub code: Option<String>,
+    pub ip: Option<String>,
+}
+
+pub fn parse_router_line(line: &LogLine) -> Option<RouterRequest> {
+    if line.source != "heroku" || line.process != "router" {
+        return None;
+    }
+
+    let values = parse_key_values(&line.message);
+
+    Some(RouterRequest {
+        path: values.get("path").cloned(),
+        status: values.get("status").and_then(|status| status.parse().ok()),
+        service_ms: values
+            .get("service")
+            .and_then(|service| service.trim_end_matches("ms").parse().ok()),
+        code: values.get("code").cloned(),
+        // Heroku's router appends the address that connected to it, and
+        // that is the one apps check BLOCKED_IPS against. Anything before it
+        // came from the client's own X-Forwarded-For header, which it can fake
+        ip: values
+            .get("fwd")
+            .and_then(|fwd| fwd.rsplit(',').next())
+            .map(|ip| ip.trim().to_string()),
+    })
+}
+
+// The errors a line counts towards: router error codes (like H12),
+// "5xx" for server errors, and dyno memory errors (R14 and R15)
+pub fn error_codes(line: &LogLine) -> Vec<String> {
+    if let Some(request) = parse_router_line(line) {
+        let mut codes: Vec<String> = request.code.into_iter().collect();
+
+        if matches!(request.status, Some(status) if status >= 500) {
+            codes.push(String::from("5xx"));
+        }
+
+        return codes;
+    }
+
+    if line.source == "heroku" {
+        for code in &["R14", "R15"] {
+            if line.message.starts_with(&format!("Error {}", code)) {
+                return vec![code.to_string()];
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub app_name: String,
+    pub code: String,
+    pub count: usize,
+    pub window: Duration,
+    pub average_service_ms: Option<u64>,
+}
+
+impl Alert {
+    pub fn message(&self) -> String {
+        let mut message = format!(
+            "{}: {} {} errors in the last {} minutes",
+            self.app_name,
+            self.count,
+            self.code,
+            self.window.as_secs() / 60
+        );
+
+        if let Some(average) = self.average_service_ms {
+            message.push_str(&format!(" (average service time {}ms)", average));
+        }
+
+        message
+    }
+}
+
+// A request from a client, as seen by the router
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouterHit {
+    pub at: DateTime<Utc>,
+    pub ip: String,
+    pub path: String,
+    pub status: Option<u16>,
+}
+
+impl RouterHit {
+    pub fn from_line(at: DateTime<Utc>, line: &LogLine) -> Option<RouterHit> {
+        let request = parse_router_line(line)?;
+
+        Some(RouterHit {
+            at,
+            ip: request.ip?,
+            path: request.path.unwrap_or_default(),
+            status: request.status,
+        })
+    }
+}
+
+// The requests one client IP made, for top_ips
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpTraffic {
+    pub ip: String,
+    pub requests: usize,
+    // Requests that got a 4xx or 5xx status
+    pub errors: usize,
+    pub top_path: String,
+}
+
+impl IpTraffic {
+    pub fn error_rate(&self) -> f64 {
+        self.errors as f64 / self.requests as f64
+    }
+}
+
+// Ranks the client IPs by how many requests they made, then by their error rate
+pub fn rank_ips(hits: &[RouterHit], limit: usize) -> Vec<IpTraffic> {
+    let mut by_ip: HashMap<&str, (usize, usize, HashMap<&str, usize>)> = HashMap::new();
+
+    for hit in hits {
+        let (requests, errors, paths) = by_ip.entry(&hit.ip).or_default();
+
+        *requests += 1;
+        *paths.entry(&hit.path).or_default() += 1;
+
+        if matches!(hit.status, Some(status) if status >= 400) {
+            *errors += 1;
+        }
+    }
+
+    let mut ranked: Vec<IpTraffic> = by_ip
+        .into_iter()
+        .map(|(ip, (requests, errors, paths))| IpTraffic {
+            ip: ip.to_string(),
+            requests,
+            errors,
+            top_path: paths
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))
+                .map(|(path, _)| path.to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then(b.errors.cmp(&a.errors))
+            .then(a.ip.cmp(&b.ip))
+    });
+    ranked.truncate(limit);
+    ranked
+}
+
+// How long, and how many, requests are kept per app for top_ips
+pub const TRAFFIC_RETENTION: Duration = Duration::from_secs(60 * 60);
+const MAX_TRAFFIC_HITS: usize = 100_000;
+
+#[derive(Debug, Default)]
+struct AppStats {
+    errors: HashMap<String, VecDeque<DateTime<Utc>>>,
+    last_alerts: HashMap<String, DateTime<Utc>>,
+    service_times: VecDeque<(DateTime<Utc>, u64)>,
+    traffic: VecDeque<RouterHit>,
+}
+
+// Rolling counters of the errors seen in each app's log drain
+#[derive(Debug, Default)]
+pub struct DrainStats {
+    apps: Mutex<HashMap<String, AppStats>>,
+}
+
+impl DrainStats {
+    // Counts the lines towards the app's errors, returning an alert for every
+    // error that reached its threshold within the window. An error alerts
+    // at most once per window
+    pub fn record(
+        &self,
+        app_name: &str,
+        lines: &[LogLine],
+        thresholds: &HashMap<String, usize>,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Vec<Alert> {
+        let window_start = now - chrono::Duration::from_std(window).unwrap();
+
+        let mut apps = self.apps.lock().unwrap();
+        let stats = apps.entry(app_name.to_string()).or_default();
+
+        for line in lines {
+            if let Some(service_ms) = parse_router_line(line).and_then(|r| r.service_ms) {
+                stats.service_times.push_back((now, service_ms));
+            }
+
+            for code in error_codes(line) {
+                stats.errors.entry(code).or_default().push_back(now);
+            }
+
+            if let Some(hit) = RouterHit::from_line(now, line) {
+                stats.traffic.push_back(hit);
+            }
+        }
+
+        let traffic_start = now - chrono::Duration::from_std(TRAFFIC_RETENTION).unwrap();
+
+        while stats.traffic.len() > MAX_TRAFFIC_HITS
+            || matches!(stats.traffic.front(), Some(hit) if hit.at < traffic_start)
+        {
+            stats.traffic.pop_front();
+        }
+
+        while matches!(stats.service_times.front(), Some((at, _)) if *at < window_start) {
+            stats.service_times.pop_front();
+        }
+
+        let average_service_ms = if stats.service_times.is_empty() {
+            None
+        } else {
+            let total: u64 = stats.service_times.iter().map(|(_, ms)| ms).sum();
+            Some(total / stats.service_times.len() as u64)
+        };
+
+        let mut alerts = Vec::new();
+
+        for (code, times) in stats.errors.iter_mut() {
+            while matches!(times.front(), Some(at) if *at < window_start) {
+                times.pop_front();
+            }
+
+            let threshold = match thresholds.get(code) {
+                Some(threshold) => *threshold,
+                None => continue,
+            };
+
+            let recently_alerted =
+                matches!(stats.last_alerts.get(code), Some(at) if *at >= window_start);
+
+            if times.len() >= threshold && !recently_alerted {
+                stats.last_alerts.insert(code.clone(), now);
+
+                alerts.push(Alert {
+                    app_name: app_name.to_string(),
+                    code: code.clone(),
+                    count: times.len(),
+                    window,
+                    average_service_ms,
+                });
+            }
+        }
+
+        alerts.sort_by(|a, b| a.code.cmp(&b.code));
+        alerts
+    }
+}
+
+impl DrainStats {
+    // The requests the drain has seen for an app since a time,
+    // going back at most an hour
+    pub fn traffic_since(&self, app_name: &str, since: DateTime<Utc>) -> Vec<RouterHit> {
+        self.apps
+            .lock()
+            .unwrap()
+            .get(app_name)
+            .map(|stats| {
+                stats
+                    .traffic
+                    .iter()
+                    .filter(|hit| hit.at >= since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl TypeMapKey for DrainStats {
+    type Value = Arc<DrainStats>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: &str) -> String {
+        format!("{} {}", message.len(), message)
+    }
+
+    fn router(message: &str) -> String {
+        frame(&format!(
+            "<158>1 2020-05-04T12:00:00+00:00 host heroku router - {}",
+            message
+        ))
+    }
+
+    fn thresholds() -> HashMap<String, usize> {
+        vec![("H12".to_string(), 2), ("5xx".to_string(), 3)]
+            .into_iter()
+            .collect()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_octet_counted_frames() {
+        let body = format!(
+            "{}\n{}",
+            frame("<40>1 2020-05-04T12:00:00+00:00 host app web.3 - State changed from starting to up"),
+            frame("<45>1 2020-05-04T12:00:01+00:00 host heroku web.1 - Error R14 (Memory quota exceeded)")
+        );
+
+        let lines = parse_drain_body(&body);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].source, "app");
+        assert_eq!(lines[0].process, "web.3");
+        assert_eq!(lines[0].message, "State changed from starting to up");
+        assert_eq!(error_codes(&lines[1]), vec!["R14"]);
+        assert!(parse_drain_body("not a drain").is_empty());
+    }
+
+    #[test]
+    fn parse_router_lines() {
+        let lines = parse_drain_body(&router(
+            "at=error code=H12 desc=\"Request timeout\" method=GET path=\"/api/v1/crates\" \
+             fwd=\"1.2.3.4\" dyno=web.1 connect=1ms service=30001ms status=503 bytes=0",
+        ));
+
+        let request = parse_router_line(&lines[0]).unwrap();
+
+        assert_eq!(request.status, Some(503));
+        assert_eq!(request.service_ms, Some(30001));
+        assert_eq!(request.code.as_deref(), Some("H12"));
+        assert_eq!(request.ip.as_deref(), Some("1.2.3.4"));
+        assert_eq!(error_codes(&lines[0]), vec!["H12", "5xx"]);
+    }
+
+    #[test]
+    fn rank_by_the_address_the_router_saw() {
+        // The client sent "X-Forwarded-For: 6.6.6.6" to pin its requests on someone else
+        let lines = parse_drain_body(&router(
+            "at=info method=GET path=\"/\" fwd=\"6.6.6.6, 1.2.3.4\" status=200",
+        ));
+
+        let request = parse_router_line(&lines[0]).unwrap();
+
+        assert_eq!(request.ip.as_deref(), Some("1.2.3.4"));
+    }
+
+    #[test]
+    fn parse_log_session_lines() {
+        let (at, line) = parse_log_session_line(
+            "2020-05-04T12:00:00.123456+00:00 heroku[router]: at=info method=GET path=\"/\" \
+             fwd=\"1.2.3.4\" status=200",
+        )
+        .unwrap();
+
+        assert_eq!(at.to_rfc3339(), "2020-05-04T12:00:00.123456+00:00");
+        assert_eq!(line.source, "heroku");
+        assert_eq!(line.process, "router");
+
+        let hit = RouterHit::from_line(at, &line).unwrap();
+        assert_eq!(hit.ip, "1.2.3.4");
+        assert_eq!(hit.path, "/");
+        assert_eq!(hit.status, Some(200));
+
+        assert!(parse_log_session_line("not a log line").is_none());
+    }
+
+    #[test]
+    fn rank_client_ips() {
+        let hit = |ip: &str, path: &str, status: u16| RouterHit {
+            at: at("2020-05-04T12:00:00Z"),
+            ip: ip.to_string(),
+            path: path.to_string(),
+            status: Some(status),
+        };
+
+        let hits = vec![
+            hit("1.2.3.4", "/api/v1/crates", 200),
+            hit("5.6.7.8", "/wp-login.php", 404),
+            hit("1.2.3.4", "/api/v1/crates", 200),
+            hit("5.6.7.8", "/.env", 404),
+            hit("5.6.7.8", "/wp-login.php", 404),
+            hit("9.9.9.9", "/", 200),
+            hit("1.2.3.4", "/", 503),
+        ];
+
+        let ranked = rank_ips(&hits, 2);
+
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].ip, "5.6.7.8");
+        assert_eq!(ranked[0].requests, 3);
+        assert_eq!(ranked[0].error_rate(), 1.0);
+        assert_eq!(ranked[0].top_path, "/wp-login.php");
+        assert_eq!(ranked[1].ip, "1.2.3.4");
+        assert_eq!(ranked[1].errors, 1);
+        assert_eq!(ranked[1].top_path, "/api/v1/crates");
+    }
+
+    #[test]
+    fn keep_recent_traffic() {
+        let stats = DrainStats::default();
+        let window = Duration::from_secs(5 * 60);
+        let request = parse_drain_body(&router("at=info path=\"/\" fwd=\"1.2.3.4\" status=200"));
+
+        stats.record(
+            "my_app",
+            &request,
+            &thresholds(),
+            window,
+            at("2020-05-04T10:59:00Z"),
+        );
+        stats.record(
+            "my_app",
+            &request,
+            &thresholds(),
+            window,
+            at("2020-05-04T12:00:00Z"),
+        );
+
+        assert_eq!(
+            stats
+                .traffic_since("my_app", at("2020-05-04T10:00:00Z"))
+                .len(),
+            1
+        );
+        assert!(stats
+            .traffic_since("my_app", at("2020-05-04T12:00:01Z"))
+            .is_empty());
+        assert!(stats
+            .traffic_since("other_app", at("2020-05-04T10:00:00Z"))
+            .is_empty());
+    }
+
+    #[test]
+    fn alert_once_per_window() {
+        let stats = DrainStats::default();
+        let window = Duration::from_secs(5 * 60);
+        let timeout = parse_drain_body(&router(
+            "at=error code=H12 desc=\"Request timeout\" service=30000ms status=503",
+        ));
+
+        assert!(stats
+            .record(
+                "my_app",
+                &timeout,
+                &thresholds(),
+                window,
+                at("2020-05-04T12:00:00Z")
+            )
+            .is_empty());
+
+        let alerts = stats.record(
+            "my_app",
+            &timeout,
+            &thresholds(),
+            window,
+            at("2020-05-04T12:01:00Z"),
+        );
+
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].code, "H12");
+        assert_eq!(alerts[0].count, 2);
+        assert_eq!(
+            alerts[0].message(),
+            "my_app: 2 H12 errors in the last 5 minutes (average service time 30000ms)"
+        );
+
+        // Already alerted within this window
+        assert!(stats
+            .record(
+                "my_app",
+                &timeout,
+                &thresholds(),
+                window,
+                at("2020-05-04T12:02:00Z")
+            )
+            .iter()
+            .all(|alert| alert.code != "H12"));
+
+        // The earlier errors have left the window
+        assert!(stats
+            .record(
+                "my_app",
+                &timeout,
+                &thresholds(),
+                window,
+                at("2020-05-04T12:10:00Z")
+            )
+            .is_empty());
+        assert!(stats
+            .record(
+                "other_app",
+                &timeout,
+                &thresholds(),
+                window,
+                at("2020-05-04T12:10:00Z")
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn match_drain_tokens() {
+        assert!(tokens_match("secret-token", "secret-token"));
+        assert!(!tokens_match("secret-tokem", "secret-token"));
+        assert!(!tokens_match("secret", "secret-token"));
+        assert!(!tokens_match("", "secret-token"));
+    }
+}
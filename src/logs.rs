@@ -0,0 +1,102 @@
+use crate::utilities::DISCORD_MESSAGE_LIMIT;
+
+// The most lines Heroku returns for a log session
+pub const MAX_LOG_LINES: usize = 1500;
+
+#[derive(Debug, PartialEq)]
+pub enum LogsReply {
+    // Short enough to post with the header as a code block in a message
+    CodeBlock(String),
+    // Too long for a message, posted as a text file instead
+    Attachment(String),
+}
+
+// Keeps the last `limit` lines, only counting lines that contain the pattern when there is one
+pub fn filter_log_lines<'a>(logs: &'a str, pattern: Option<&str>, limit: usize) -> Vec<&'a str> {
+    let matching: Vec<&str> = logs
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| match pattern {
+            Some(pattern) => line.contains(pattern),
+            None => true,
+        })
+        .collect();
+
+    matching[matching.len().saturating_sub(limit)..].to_vec()
+}
+
+pub fn logs_reply(header: &str, lines: &[&str]) -> LogsReply {
+    // Keep log lines from closing the code block early
+    let logs = lines.join("\n").replace("```", "'''");
+    let message = format!("{}\n```\n{}\n```", header, logs);
+
+    // Leave room for the mention Discord adds to replies
+    if message.len() < DISCORD_MESSAGE_LIMIT - 100 {
+        LogsReply::CodeBlock(message)
+    } else {
+        LogsReply::Attachment(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGS: &str = "\
+2020-05-04T12:00:00+00:00 app[web.1]: GET /api/v1/crates 200
+2020-05-04T12:00:01+00:00 heroku[router]: at=error code=H12 desc=\"Request timeout\"
+2020-05-04T12:00:02+00:00 app[web.1]: GET /api/v1/summary 200
+
+2020-05-04T12:00:03+00:00 heroku[router]: at=error code=H12 desc=\"Request timeout\"
+";
+
+    #[test]
+    fn keep_the_last_lines() {
+        let lines = filter_log_lines(LOGS, None, 2);
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("summary"));
+        assert_eq!(filter_log_lines(LOGS, None, 100).len(), 4);
+    }
+
+    #[test]
+    fn grep_for_a_pattern() {
+        let lines = filter_log_lines(LOGS, Some("code=H12"), 100);
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.contains("H12")));
+        assert!(filter_log_lines(LOGS, Some("H13"), 100).is_empty());
+    }
+
+    #[test]
+    fn attach_long_logs() {
+        let short = filter_log_lines(LOGS, None, 100);
+
+        match logs_reply("Here are the logs", &short) {
+            LogsReply::CodeBlock(block) => {
+                assert!(block.starts_with("Here are the logs\n```\n"));
+                assert!(block.ends_with("\n```"));
+            }
+            reply => panic!("Expected a code block, got {:?}", reply),
+        }
+
+        let long = vec!["x".repeat(100); 30];
+        let long: Vec<&str> = long.iter().map(String::as_str).collect();
+
+        assert_eq!(
+            logs_reply("Here are the logs", &long),
+            LogsReply::Attachment(long.join("\n"))
+        );
+
+        // The header counts towards the message length too
+        let header = "x".repeat(1000);
+        let medium = vec!["x".repeat(100); 9];
+        let medium: Vec<&str> = medium.iter().map(String::as_str).collect();
+
+        assert!(matches!(logs_reply("", &medium), LogsReply::CodeBlock(_)));
+        assert_eq!(
+            logs_reply(&header, &medium),
+            LogsReply::Attachment(medium.join("\n"))
+        );
+    }
+}
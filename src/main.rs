@@ -4,9 +4,13 @@
 extern crate crates_io_ops_bot;
 extern crate dotenv;
 use crates_io_ops_bot::config::Config;
+use crates_io_ops_bot::costs::parse_dyno_prices;
+use crates_io_ops_bot::deploy_locks::parse_freeze_windows;
+use crates_io_ops_bot::schedules::parse_scaling_schedules;
+use crates_io_ops_bot::utilities::{parse_app_settings, parse_duration};
 
 fn main() {
-    let config = Config::new(
+    let mut config = Config::new(
         dotenv::var("DISCORD_TOKEN").expect("DISCORD_TOKEN must be set"),
         dotenv::var("HEROKU_API_KEY").expect("HEROKU_API_KEY must be set"),
         dotenv::var("BUILD_CHECK_INTERVAL").expect("BUILD_CHECK_INTERVAL must be set"),
@@ -16,5 +20,106 @@ fn main() {
         dotenv::var("GITHUB_TOKEN").expect("GITHUB_TOKEN must be set"),
     );
 
+    if let Ok(state_file) = dotenv::var("STATE_FILE") {
+        config.state_file = state_file;
+    }
+
+    if let Ok(freeze_windows) = dotenv::var("DEPLOY_FREEZE_WINDOWS") {
+        config.freeze_windows =
+            parse_freeze_windows(&freeze_windows).expect("DEPLOY_FREEZE_WINDOWS is invalid");
+    }
+
+    if let Ok(app_buildpacks) = dotenv::var("APP_BUILDPACKS") {
+        config.app_buildpacks = parse_app_settings(&app_buildpacks)
+            .expect("APP_BUILDPACKS is invalid")
+            .into_iter()
+            .map(|(app_name, buildpacks)| {
+                let buildpacks = buildpacks.split(',').map(|b| b.trim().to_string());
+                (app_name, buildpacks.collect())
+            })
+            .collect();
+    }
+
+    if let Ok(app_max_dynos) = dotenv::var("APP_MAX_DYNOS") {
+        config.app_max_dynos = parse_app_settings(&app_max_dynos)
+            .expect("APP_MAX_DYNOS is invalid")
+            .into_iter()
+            .map(|(app_name, max)| (app_name, max.parse().expect("APP_MAX_DYNOS is invalid")))
+            .collect();
+    }
+
+    if let Ok(app_dyno_sizes) = dotenv::var("APP_DYNO_SIZES") {
+        config.app_dyno_sizes = parse_app_settings(&app_dyno_sizes)
+            .expect("APP_DYNO_SIZES is invalid")
+            .into_iter()
+            .map(|(app_name, sizes)| {
+                let sizes = sizes.split(',').map(|s| s.trim().to_string());
+                (app_name, sizes.collect())
+            })
+            .collect();
+    }
+
+    if let Ok(production_apps) = dotenv::var("PRODUCTION_APPS") {
+        config.production_apps = production_apps
+            .split(',')
+            .map(str::trim)
+            .filter(|app_name| !app_name.is_empty())
+            .map(String::from)
+            .collect();
+    }
+
+    if let Ok(scaling_schedules) = dotenv::var("SCALING_SCHEDULES") {
+        config.scaling_schedules =
+            parse_scaling_schedules(&scaling_schedules).expect("SCALING_SCHEDULES is invalid");
+    }
+
+    if let Ok(channel_id) = dotenv::var("ANNOUNCEMENT_CHANNEL_ID") {
+        if !channel_id.is_empty() {
+            config.announcement_channel_id = Some(
+                channel_id
+                    .parse()
+                    .expect("ANNOUNCEMENT_CHANNEL_ID is invalid"),
+            );
+        }
+    }
+
+    if let Ok(dyno_prices) = dotenv::var("DYNO_PRICES") {
+        config.dyno_prices = parse_dyno_prices(&dyno_prices).expect("DYNO_PRICES is invalid");
+    }
+
+    if let Ok(log_drain_address) = dotenv::var("LOG_DRAIN_ADDRESS") {
+        if !log_drain_address.is_empty() {
+            config.log_drain_address = Some(log_drain_address);
+        }
+    }
+
+    if let Ok(log_drain_token) = dotenv::var("LOG_DRAIN_TOKEN") {
+        if !log_drain_token.is_empty() {
+            config.log_drain_token = Some(log_drain_token);
+        }
+    }
+
+    if let Ok(channel_id) = dotenv::var("ALERT_CHANNEL_ID") {
+        if !channel_id.is_empty() {
+            config.alert_channel_id =
+                Some(channel_id.parse().expect("ALERT_CHANNEL_ID is invalid"));
+        }
+    }
+
+    if let Ok(thresholds) = dotenv::var("LOG_ALERT_THRESHOLDS") {
+        config.log_alert_thresholds = parse_app_settings(&thresholds)
+            .expect("LOG_ALERT_THRESHOLDS is invalid")
+            .into_iter()
+            .map(|(code, threshold)| {
+                let threshold = threshold.parse().expect("LOG_ALERT_THRESHOLDS is invalid");
+                (code, threshold)
+            })
+            .collect();
+    }
+
+    if let Ok(window) = dotenv::var("LOG_ALERT_WINDOW") {
+        config.log_alert_window = parse_duration(&window).expect("LOG_ALERT_WINDOW is invalid");
+    }
+
     crates_io_ops_bot::run(config)
 }
@@ -0,0 +1,86 @@
+// Heroku describes config var changes in release descriptions
+// like "Set FOO, BAR config vars" or "Remove FOO config var"
+pub fn changed_config_vars(description: &str) -> Vec<String> {
+    let keys = ["Set ", "Remove ", "Update "]
+        .iter()
+        .find_map(|prefix| description.strip_prefix(prefix))
+        .and_then(|rest| {
+            rest.strip_suffix(" config vars")
+                .or_else(|| rest.strip_suffix(" config var"))
+        });
+
+    match keys {
+        Some(keys) => keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(String::from)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AddonChange {
+    Attached(String),
+    Detached(String),
+}
+
+// Heroku describes add-on attachments in release descriptions like
+// "Attach DATABASE (@ref:postgresql-curved-12345)", the attachment
+// name followed by the add-on it points to
+pub fn addon_change(description: &str) -> Option<AddonChange> {
+    let (attached, rest) = if let Some(rest) = description.strip_prefix("Attach ") {
+        (true, rest)
+    } else if let Some(rest) = description.strip_prefix("Detach ") {
+        (false, rest)
+    } else {
+        return None;
+    };
+
+    let attachment = rest.trim().replacen("(@ref:", "(", 1);
+
+    if attached {
+        Some(AddonChange::Attached(attachment))
+    } else {
+        Some(AddonChange::Detached(attachment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_vars_from_release_descriptions() {
+        assert_eq!(changed_config_vars("Set FOO config var"), vec!["FOO"]);
+        assert_eq!(
+            changed_config_vars("Set FOO, BAR config vars"),
+            vec!["FOO", "BAR"]
+        );
+        assert_eq!(
+            changed_config_vars("Remove BLOCKED_IPS config var"),
+            vec!["BLOCKED_IPS"]
+        );
+        assert!(changed_config_vars("Deploy 2b8c1f0").is_empty());
+        assert!(changed_config_vars("Rollback to v5").is_empty());
+    }
+
+    #[test]
+    fn addon_changes_from_release_descriptions() {
+        assert_eq!(
+            addon_change("Attach DATABASE (@ref:postgresql-curved-12345)"),
+            Some(AddonChange::Attached(
+                "DATABASE (postgresql-curved-12345)".to_string()
+            ))
+        );
+        assert_eq!(
+            addon_change("Detach HEROKU_POSTGRESQL_RED (@ref:postgresql-flat-67890)"),
+            Some(AddonChange::Detached(
+                "HEROKU_POSTGRESQL_RED (postgresql-flat-67890)".to_string()
+            ))
+        );
+        assert!(addon_change("Set DATABASE_URL config var").is_none());
+        assert!(addon_change("Deploy 2b8c1f0").is_none());
+    }
+}
@@ -0,0 +1,134 @@
+// The most releases Heroku returns for one request
+pub const MAX_RELEASES: usize = 1000;
+
+// Heroku lists releases oldest first, 200 at a time, so an app with a long
+// history would never show its recent releases. This Range header asks for
+// the newest ones first instead
+pub fn newest_releases_range(max: usize) -> String {
+    format!("version ..; order=desc, max={}", max)
+}
+
+// Works out which Heroku release version a reference like "v5", "previous",
+// "-2" or "last-good" points to. Releases are given as (version, succeeded)
+// pairs, where succeeded means the release (and its release phase, which
+// runs our health checks) finished successfully
+pub fn resolve_release_target(
+    target: &str,
+    current_version: i64,
+    releases: &[(i64, bool)],
+) -> Result<i64, String> {
+    let mut earlier: Vec<(i64, bool)> = releases
+        .iter()
+        .cloned()
+        .filter(|&(version, _)| version < current_version)
+        .collect();
+    earlier.sort_by_key(|&(version, _)| version);
+
+    let steps_back = match target {
+        "previous" => Some(1),
+        "last-good" => {
+            return earlier
+                .iter()
+                .rev()
+                .find(|&&(_, succeeded)| succeeded)
+                .map(|&(version, _)| version)
+                .ok_or_else(|| {
+                    format!("There is no successful release before v{}", current_version)
+                })
+        }
+        _ if target.starts_with('-') => Some(
+            target[1..]
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid release target: {}", target))?,
+        ),
+        _ => None,
+    };
+
+    if let Some(steps_back) = steps_back {
+        if steps_back == 0 || steps_back > earlier.len() {
+            return Err(format!(
+                "There are only {} releases before v{}",
+                earlier.len(),
+                current_version
+            ));
+        }
+
+        return Ok(earlier[earlier.len() - steps_back].0);
+    }
+
+    let version = target
+        .trim_start_matches('v')
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid release target: {}", target))?;
+
+    if !releases.iter().any(|&(known, _)| known == version) {
+        return Err(format!("Release v{} does not exist", version));
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn releases() -> Vec<(i64, bool)> {
+        vec![(1, true), (2, true), (3, false), (4, true), (5, false)]
+    }
+
+    #[test]
+    fn resolve_explicit_versions() {
+        assert_eq!(resolve_release_target("v2", 5, &releases()).unwrap(), 2);
+        assert_eq!(resolve_release_target("2", 5, &releases()).unwrap(), 2);
+        assert!(resolve_release_target("v9", 5, &releases()).is_err());
+        assert!(resolve_release_target("latest", 5, &releases()).is_err());
+    }
+
+    #[test]
+    fn resolve_relative_versions() {
+        assert_eq!(
+            resolve_release_target("previous", 5, &releases()).unwrap(),
+            4
+        );
+        assert_eq!(resolve_release_target("-2", 5, &releases()).unwrap(), 3);
+        assert_eq!(resolve_release_target("-4", 5, &releases()).unwrap(), 1);
+        assert!(resolve_release_target("-5", 5, &releases()).is_err());
+        assert!(resolve_release_target("-0", 5, &releases()).is_err());
+    }
+
+    #[test]
+    fn resolve_against_the_newest_releases_of_a_long_history() {
+        // The newest 250 of 300 releases, as Heroku returns them for the
+        // Range header, where the oldest 200 would not reach the current one
+        let releases: Vec<(i64, bool)> = (51..=300).rev().map(|version| (version, true)).collect();
+
+        assert_eq!(
+            resolve_release_target("previous", 300, &releases).unwrap(),
+            299
+        );
+        assert_eq!(resolve_release_target("-210", 300, &releases).unwrap(), 90);
+        assert_eq!(resolve_release_target("v60", 300, &releases).unwrap(), 60);
+        assert!(resolve_release_target("-250", 300, &releases).is_err());
+    }
+
+    #[test]
+    fn ask_for_the_newest_releases_first() {
+        assert_eq!(
+            newest_releases_range(MAX_RELEASES),
+            "version ..; order=desc, max=1000"
+        );
+    }
+
+    #[test]
+    fn resolve_last_good_release() {
+        assert_eq!(
+            resolve_release_target("last-good", 5, &releases()).unwrap(),
+            4
+        );
+        assert_eq!(
+            resolve_release_target("last-good", 4, &releases()).unwrap(),
+            2
+        );
+        assert!(resolve_release_target("last-good", 1, &releases()).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use heroku_rs::endpoints::custom::CustomEndpoint;
+use heroku_rs::framework::endpoint::Method;
+
+use serde::Serialize;
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuantityChange {
+    // An absolute number of dynos, like "3"
+    Set(i32),
+    // A number of dynos to add or remove, like "+2" or "-1"
+    Add(i32),
+}
+
+impl QuantityChange {
+    pub fn resolve(&self, current: i32) -> Result<i32, String> {
+        let quantity = match *self {
+            QuantityChange::Set(quantity) => quantity,
+            QuantityChange::Add(change) => current
+                .checked_add(change)
+                .ok_or_else(|| format!("Can't change {} dynos by {}", current, change))?,
+        };
+
+        if quantity < 0 {
+            return Err(format!(
+                "Can't scale to {} dynos, there are only {} running",
+                quantity, current
+            ));
+        }
+
+        Ok(quantity)
+    }
+}
+
+// What a scale_app command asks to change about a formation.
+// Anything left as None keeps its current value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleChange {
+    pub formation: String,
+    pub quantity: Option<QuantityChange>,
+    pub size: Option<String>,
+}
+
+// Parses the arguments after the app name. Accepts "web=3", "web +2",
+// "web 3", a size on its own ("web standard-2X") and "web 3 standard-1X",
+// or several formations at once like "web=4:standard-2X worker=2"
+pub fn parse_scale_changes(args: &[String]) -> Result<Vec<ScaleChange>, String> {
+    if args.is_empty() || !args.iter().all(|arg| arg.contains('=')) {
+        return Ok(vec![parse_scale_change(args)?]);
+    }
+
+    let mut changes: Vec<ScaleChange> = Vec::new();
+
+    for arg in args {
+        let change = parse_formation_assignment(arg)?;
+
+        if changes
+            .iter()
+            .any(|other| other.formation == change.formation)
+        {
+            return Err(format!(
+                "The {} formation is listed more than once",
+                change.formation
+            ));
+        }
+
+        changes.push(change);
+    }
+
+    Ok(changes)
+}
+
+fn parse_scale_change(args: &[String]) -> Result<ScaleChange, String> {
+    match args {
+        [formation, quantity_or_size] => {
+            let formation = formation_name(formation)?;
+
+            if looks_like_quantity(quantity_or_size) {
+                Ok(ScaleChange {
+                    formation,
+                    quantity: Some(parse_quantity(quantity_or_size)?),
+                    size: None,
+                })
+            } else {
+                Ok(ScaleChange {
+                    formation,
+                    quantity: None,
+                    size: Some(quantity_or_size.to_string()),
+                })
+            }
+        }
+        [formation, quantity, size] => Ok(ScaleChange {
+            formation: formation_name(formation)?,
+            quantity: Some(parse_quantity(quantity)?),
+            size: Some(size.to_string()),
+        }),
+        _ => Err(String::from(
+            "You must include a formation and a quantity and/or size, like web=3, web +2 or web standard-2X",
+        )),
+    }
+}
+
+// A formation given as "web=3" or "web=4:standard-2X"
+pub fn parse_formation_assignment(assignment: &str) -> Result<ScaleChange, String> {
+    let mut parts = assignment.splitn(2, '=');
+    let name = parts.next().unwrap_or_default();
+    let mut value = parts.next().unwrap_or_default().splitn(2, ':');
+    let quantity = value.next().unwrap_or_default();
+    let size = value.next();
+
+    if size == Some("") {
+        return Err(format!("Invalid dyno size in {}", assignment));
+    }
+
+    Ok(ScaleChange {
+        formation: formation_name(name)?,
+        quantity: Some(parse_quantity(quantity)?),
+        size: size.map(String::from),
+    })
+}
+
+fn formation_name(name: &str) -> Result<String, String> {
+    if name.is_empty() {
+        return Err(String::from("You must include a formation name"));
+    }
+
+    Ok(name.to_string())
+}
+
+fn looks_like_quantity(value: &str) -> bool {
+    value
+        .trim_start_matches(['+', '-'])
+        .chars()
+        .all(|c| c.is_ascii_digit())
+}
+
+fn parse_quantity(value: &str) -> Result<QuantityChange, String> {
+    let invalid = || format!("Invalid quantity: {}", value);
+
+    if value.starts_with('+') || value.starts_with('-') {
+        let digits = &value[1..];
+
+        // i32 parsing would also accept a second sign, like in --3
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let change = digits.parse::<i32>().map_err(|_| invalid())?;
+
+        if value.starts_with('-') {
+            Ok(QuantityChange::Add(-change))
+        } else {
+            Ok(QuantityChange::Add(change))
+        }
+    } else {
+        value
+            .parse::<i32>()
+            .map(QuantityChange::Set)
+            .map_err(|_| invalid())
+    }
+}
+
+// Checks a new quantity and/or size against the limits configured for the app
+pub fn check_scaling_limits(
+    config: &Config,
+    app_name: &str,
+    quantity: Option<i32>,
+    size: Option<&str>,
+) -> Result<(), String> {
+    if let (Some(quantity), Some(max)) = (quantity, config.app_max_dynos.get(app_name)) {
+        if quantity > *max {
+            return Err(format!(
+                "{} can't be scaled above {} dynos per formation",
+                app_name, max
+            ));
+        }
+    }
+
+    if let (Some(size), Some(allowed)) = (size, config.app_dyno_sizes.get(app_name)) {
+        if !allowed
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(size))
+        {
+            return Err(format!(
+                "{} is not an allowed dyno size for {} (allowed: {})",
+                size,
+                app_name,
+                allowed.join(", ")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// A formation's quantity and size before and after scaling
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormationChange {
+    pub formation: String,
+    pub before: (i32, String),
+    pub after: (i32, String),
+}
+
+// Lines up the changes to several formations in a code block
+pub fn formation_changes_table(changes: &[FormationChange]) -> String {
+    let describe = |(quantity, size): &(i32, String)| format!("{} x {}", quantity, size);
+
+    let mut rows = vec![(
+        String::from("Formation"),
+        String::from("Before"),
+        String::from("After"),
+    )];
+
+    for change in changes {
+        rows.push((
+            change.formation.clone(),
+            describe(&change.before),
+            describe(&change.after),
+        ));
+    }
+
+    let formation_width = rows.iter().map(|row| row.0.len()).max().unwrap_or_default();
+    let before_width = rows.iter().map(|row| row.1.len()).max().unwrap_or_default();
+
+    let mut table = String::from("```\n");
+
+    for (formation, before, after) in rows {
+        table.push_str(&format!(
+            "{:formation_width$}  {:before_width$}  {}\n",
+            formation,
+            before,
+            after,
+            formation_width = formation_width,
+            before_width = before_width,
+        ));
+    }
+
+    table.push_str("```");
+    table
+}
+
+// Scaling a production app's web formation to zero takes it offline
+pub fn needs_confirmation(config: &Config, app_name: &str, formation: &str, quantity: i32) -> bool {
+    quantity == 0 && formation == "web" && config.production_apps.contains(app_name)
+}
+
+// One formation in a batch update, leaving out whatever isn't changing
+#[derive(Debug, Clone, Serialize)]
+pub struct FormationBatchEntry {
+    #[serde(rename = "type")]
+    pub formation: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FormationBatchUpdate {
+    pub updates: Vec<FormationBatchEntry>,
+}
+
+// heroku_rs has no endpoint for updating several formations at once, so
+// scale_app sends Heroku's batch update itself. The response is the app's
+// formations as a JSON array
+pub fn formation_batch_update(
+    app_name: &str,
+    updates: Vec<FormationBatchEntry>,
+) -> CustomEndpoint<FormationBatchUpdate> {
+    CustomEndpoint::new(
+        format!("apps/{}/formation", app_name),
+        Method::Patch,
+        FormationBatchUpdate { updates },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heroku_rs::framework::endpoint::HerokuEndpoint;
+    use serde_json::json;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn config() -> Config {
+        let mut config = Config::new(
+            "discord".to_string(),
+            "heroku".to_string(),
+            "5".to_string(),
+            "30".to_string(),
+            "org".to_string(),
+            "repo".to_string(),
+            "github".to_string(),
+        );
+
+        config.app_max_dynos.insert("my_app".to_string(), 5);
+        config.app_dyno_sizes.insert(
+            "my_app".to_string(),
+            vec!["standard-1X".to_string(), "standard-2X".to_string()],
+        );
+        config.production_apps.insert("my_app".to_string());
+        config
+    }
+
+    #[test]
+    fn parse_scale_forms() {
+        assert_eq!(
+            parse_scale_changes(&args(&["web=3"])).unwrap(),
+            vec![ScaleChange {
+                formation: "web".to_string(),
+                quantity: Some(QuantityChange::Set(3)),
+                size: None,
+            }]
+        );
+        assert_eq!(
+            parse_scale_change(&args(&["web", "+2"])).unwrap().quantity,
+            Some(QuantityChange::Add(2))
+        );
+        assert_eq!(
+            parse_scale_change(&args(&["web", "-1"])).unwrap().quantity,
+            Some(QuantityChange::Add(-1))
+        );
+
+        let size_only = parse_scale_change(&args(&["worker", "standard-2X"])).unwrap();
+        assert_eq!(size_only.quantity, None);
+        assert_eq!(size_only.size.unwrap(), "standard-2X");
+
+        let full = parse_scale_change(&args(&["web", "3", "standard-1X"])).unwrap();
+        assert_eq!(full.quantity, Some(QuantityChange::Set(3)));
+        assert_eq!(full.size.unwrap(), "standard-1X");
+    }
+
+    #[test]
+    fn parse_several_formations() {
+        let changes = parse_scale_changes(&args(&["web=4:standard-2X", "worker=+2"])).unwrap();
+
+        assert_eq!(
+            changes,
+            vec![
+                ScaleChange {
+                    formation: "web".to_string(),
+                    quantity: Some(QuantityChange::Set(4)),
+                    size: Some("standard-2X".to_string()),
+                },
+                ScaleChange {
+                    formation: "worker".to_string(),
+                    quantity: Some(QuantityChange::Add(2)),
+                    size: None,
+                },
+            ]
+        );
+        assert_eq!(parse_scale_changes(&args(&["web", "+2"])).unwrap().len(), 1);
+        assert!(parse_scale_changes(&args(&["web=2", "web=3"])).is_err());
+        assert!(parse_scale_changes(&args(&["web=2:"])).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_scale_forms() {
+        assert!(parse_scale_changes(&args(&["web"])).is_err());
+        assert!(parse_scale_changes(&args(&["web=many"])).is_err());
+        assert!(parse_scale_changes(&args(&["=3"])).is_err());
+        assert!(parse_scale_changes(&args(&["web=--3"])).is_err());
+        assert!(parse_scale_changes(&args(&["web=+-2"])).is_err());
+        assert!(parse_scale_changes(&args(&["web=+"])).is_err());
+        assert!(parse_scale_changes(&args(&["web", "three", "standard-1X"])).is_err());
+    }
+
+    #[test]
+    fn before_and_after_table() {
+        let table = formation_changes_table(&[
+            FormationChange {
+                formation: "web".to_string(),
+                before: (2, "standard-1X".to_string()),
+                after: (4, "standard-2X".to_string()),
+            },
+            FormationChange {
+                formation: "worker".to_string(),
+                before: (1, "standard-1X".to_string()),
+                after: (2, "standard-1X".to_string()),
+            },
+        ]);
+
+        assert_eq!(
+            table,
+            "```\n\
+             Formation  Before           After\n\
+             web        2 x standard-1X  4 x standard-2X\n\
+             worker     1 x standard-1X  2 x standard-1X\n\
+             ```"
+        );
+    }
+
+    #[test]
+    fn resolve_relative_quantities() {
+        assert_eq!(QuantityChange::Add(2).resolve(3).unwrap(), 5);
+        assert_eq!(QuantityChange::Add(-3).resolve(3).unwrap(), 0);
+        assert_eq!(QuantityChange::Set(1).resolve(3).unwrap(), 1);
+        assert!(QuantityChange::Add(-4).resolve(3).is_err());
+        assert!(QuantityChange::Add(i32::MAX).resolve(3).is_err());
+    }
+
+    #[test]
+    fn enforce_configured_limits() {
+        let config = config();
+
+        assert!(check_scaling_limits(&config, "my_app", Some(5), Some("standard-2x")).is_ok());
+        assert!(check_scaling_limits(&config, "my_app", Some(6), None).is_err());
+        assert!(check_scaling_limits(&config, "my_app", None, Some("performance-l")).is_err());
+        assert!(
+            check_scaling_limits(&config, "other_app", Some(50), Some("performance-l")).is_ok()
+        );
+    }
+
+    #[test]
+    fn confirm_scaling_production_web_to_zero() {
+        let config = config();
+
+        assert!(needs_confirmation(&config, "my_app", "web", 0));
+        assert!(!needs_confirmation(&config, "my_app", "worker", 0));
+        assert!(!needs_confirmation(&config, "my_app", "web", 1));
+        assert!(!needs_confirmation(&config, "other_app", "web", 0));
+    }
+
+    #[test]
+    fn batch_update_formations_in_one_request() {
+        let endpoint = formation_batch_update(
+            "my_app",
+            vec![
+                FormationBatchEntry {
+                    formation: "web".to_string(),
+                    quantity: Some(3),
+                    size: Some("standard-2x".to_string()),
+                },
+                FormationBatchEntry {
+                    formation: "worker".to_string(),
+                    quantity: Some(0),
+                    size: None,
+                },
+            ],
+        );
+
+        assert_eq!(endpoint.path(), "apps/my_app/formation");
+        assert!(matches!(endpoint.method(), Method::Patch));
+        assert_eq!(
+            serde_json::to_value(endpoint.body().unwrap()).unwrap(),
+            json!({
+                "updates": [
+                    {"type": "web", "quantity": 3, "size": "standard-2x"},
+                    {"type": "worker", "quantity": 0},
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+
+use crate::scaling::{parse_formation_assignment, QuantityChange};
+
+// A cron expression with the usual five fields (minute, hour, day of
+// month, month, day of week), evaluated in UTC. Fields can be "*",
+// numbers, ranges ("1-5"), lists ("0,30") and steps ("*/15")
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    days_of_week: BTreeSet<u32>,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+
+        if fields.len() != 5 {
+            return Err(format!(
+                "Invalid schedule `{}`, it must have 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        }
+
+        // Sunday is both 0 and 7 in the day of week field
+        let days_of_week = parse_cron_field(fields[4], 0, 7)?
+            .into_iter()
+            .map(|day| day % 7)
+            .collect();
+
+        Ok(CronSchedule {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days_of_month: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = self.days_of_month.contains(&time.day());
+        let day_of_week = self
+            .days_of_week
+            .contains(&time.weekday().num_days_from_sunday());
+
+        // Like cron, when both day fields are restricted either one can match
+        let day = if self.any_day_of_month || self.any_day_of_week {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        };
+
+        day && self.minutes.contains(&time.minute())
+            && self.hours.contains(&time.hour())
+            && self.months.contains(&time.month())
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, String> {
+    let invalid = || format!("Invalid schedule field: {}", field);
+    let mut values = BTreeSet::new();
+
+    for part in field.split(',') {
+        let mut range_and_step = part.splitn(2, '/');
+        let range = range_and_step.next().ok_or_else(invalid)?;
+        let step = match range_and_step.next() {
+            Some(step) => step.parse::<u32>().map_err(|_| invalid())?,
+            None => 1,
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(dash) = range.find('-') {
+            (
+                range[..dash].parse::<u32>().map_err(|_| invalid())?,
+                range[dash + 1..].parse::<u32>().map_err(|_| invalid())?,
+            )
+        } else {
+            let value = range.parse::<u32>().map_err(|_| invalid())?;
+            (value, value)
+        };
+
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    Ok(values)
+}
+
+// Scales an app's formation to a fixed quantity (and optionally size)
+// whenever its cron expression matches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalingSchedule {
+    pub id: String,
+    pub app_name: String,
+    pub formation: String,
+    pub quantity: i32,
+    pub size: Option<String>,
+    pub cron: String,
+    pub created_by: String,
+    // Where to announce scheduled changes, schedules from the config
+    // announce in the channel set in ANNOUNCEMENT_CHANNEL_ID
+    pub channel_id: Option<u64>,
+}
+
+impl ScalingSchedule {
+    // Parses a schedule like "my_app web=4:standard-2X 0 8 * * 1-5"
+    pub fn parse(
+        id: &str,
+        spec: &str,
+        created_by: &str,
+        channel_id: Option<u64>,
+    ) -> Result<ScalingSchedule, String> {
+        let parts: Vec<&str> = spec.split_whitespace().collect();
+
+        if parts.len() != 7 {
+            return Err(format!(
+                "Invalid scaling schedule `{}`, it must look like `app web=4:standard-2X 0 8 * * 1-5`",
+                spec
+            ));
+        }
+
+        let change = parse_formation_assignment(parts[1])?;
+
+        let quantity = match change.quantity {
+            Some(QuantityChange::Set(quantity)) if quantity >= 0 => quantity,
+            _ => {
+                return Err(String::from(
+                    "Scheduled quantities must be absolute, like web=4",
+                ))
+            }
+        };
+
+        let cron = parts[2..].join(" ");
+        CronSchedule::parse(&cron)?;
+
+        Ok(ScalingSchedule {
+            id: id.to_string(),
+            app_name: parts[0].to_string(),
+            formation: change.formation,
+            quantity,
+            size: change.size,
+            cron,
+            created_by: created_by.to_string(),
+            channel_id,
+        })
+    }
+
+    pub fn is_due(&self, time: DateTime<Utc>) -> bool {
+        CronSchedule::parse(&self.cron)
+            .map(|cron| cron.matches(time))
+            .unwrap_or(false)
+    }
+
+    pub fn target(&self) -> String {
+        match &self.size {
+            Some(size) => format!("{} x {}", self.quantity, size),
+            None => self.quantity.to_string(),
+        }
+    }
+}
+
+// Parses the semicolon separated schedules in the SCALING_SCHEDULES
+// environmental variable. They get the IDs config-1, config-2 and so on
+pub fn parse_scaling_schedules(value: &str) -> Result<Vec<ScalingSchedule>, String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .enumerate()
+        .map(|(index, spec)| {
+            ScalingSchedule::parse(&format!("config-{}", index + 1), spec, "config", None)
+        })
+        .collect()
+}
+
+// The ID for a new schedule created through the bot
+pub fn next_schedule_id(schedules: &[ScalingSchedule]) -> String {
+    let highest = schedules
+        .iter()
+        .filter_map(|schedule| schedule.id.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0);
+
+    (highest + 1).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn match_weekday_mornings() {
+        let cron = CronSchedule::parse("0 8 * * 1-5").unwrap();
+
+        // 2020-05-04 is a Monday
+        assert!(cron.matches(at("2020-05-04T08:00:00Z")));
+        assert!(cron.matches(at("2020-05-08T08:00:30Z")));
+        assert!(!cron.matches(at("2020-05-04T08:01:00Z")));
+        assert!(!cron.matches(at("2020-05-09T08:00:00Z")));
+    }
+
+    #[test]
+    fn match_lists_steps_and_sundays() {
+        let cron = CronSchedule::parse("*/15 9,21 * * 7").unwrap();
+
+        assert!(cron.matches(at("2020-05-03T09:45:00Z")));
+        assert!(cron.matches(at("2020-05-03T21:00:00Z")));
+        assert!(!cron.matches(at("2020-05-03T09:50:00Z")));
+        assert!(!cron.matches(at("2020-05-04T09:45:00Z")));
+    }
+
+    #[test]
+    fn match_either_restricted_day() {
+        let cron = CronSchedule::parse("0 0 1 * 1").unwrap();
+
+        assert!(cron.matches(at("2020-05-01T00:00:00Z")));
+        assert!(cron.matches(at("2020-05-04T00:00:00Z")));
+        assert!(!cron.matches(at("2020-05-05T00:00:00Z")));
+    }
+
+    #[test]
+    fn reject_invalid_cron_expressions() {
+        assert!(CronSchedule::parse("0 8 * *").is_err());
+        assert!(CronSchedule::parse("60 8 * * *").is_err());
+        assert!(CronSchedule::parse("0 8 * * 5-1").is_err());
+        assert!(CronSchedule::parse("*/0 8 * * *").is_err());
+    }
+
+    #[test]
+    fn parse_schedules_from_config() {
+        let schedules = parse_scaling_schedules(
+            "my_app web=4:standard-2X 0 8 * * 1-5; my_app web=2 0 20 * * *",
+        )
+        .unwrap();
+
+        assert_eq!(schedules.len(), 2);
+        assert_eq!(schedules[0].id, "config-1");
+        assert_eq!(schedules[0].formation, "web");
+        assert_eq!(schedules[0].target(), "4 x standard-2X");
+        assert_eq!(schedules[1].cron, "0 20 * * *");
+        assert!(schedules[1].is_due(at("2020-05-04T20:00:00Z")));
+
+        assert!(parse_scaling_schedules("my_app web=+2 0 8 * * *").is_err());
+        assert!(parse_scaling_schedules("my_app web=2 0 8 * *").is_err());
+    }
+
+    #[test]
+    fn number_new_schedules() {
+        let mut schedules = parse_scaling_schedules("my_app web=2 0 20 * * *").unwrap();
+        assert_eq!(next_schedule_id(&schedules), "1");
+
+        schedules[0].id = "7".to_string();
+        assert_eq!(next_schedule_id(&schedules), "8");
+    }
+}
@@ -0,0 +1,229 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::prelude::TypeMapKey;
+
+use crate::schedules::ScalingSchedule;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+// State the bot keeps between restarts, stored as a JSON file
+// at the path set in the STATE_FILE environmental variable
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub audit_log: Vec<AuditEntry>,
+    #[serde(default)]
+    pub deploy_locks: HashMap<String, DeployLock>,
+    #[serde(default)]
+    pub scaling_schedules: Vec<ScalingSchedule>,
+    // IDs of paused schedules, from the config or created through the bot
+    #[serde(default)]
+    pub paused_schedules: BTreeSet<String>,
+    // Extra information about the entries in each app's BLOCKED_IPS
+    #[serde(default)]
+    pub blocked_ips: HashMap<String, BTreeMap<String, BlockedIp>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub user: String,
+    pub app: String,
+    pub action: String,
+    pub details: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployLock {
+    pub owner: String,
+    pub reason: String,
+    pub locked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedIp {
+    pub blocked_by: String,
+    pub blocked_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    // Where to announce the block expiring
+    #[serde(default)]
+    pub channel_id: Option<u64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl State {
+    // The (app, IP) pairs of blocks that have expired
+    pub fn expired_ip_blocks(&self, now: DateTime<Utc>) -> Vec<(String, String)> {
+        let mut expired: Vec<(String, String)> = self
+            .blocked_ips
+            .iter()
+            .flat_map(|(app_name, blocked_ips)| {
+                blocked_ips
+                    .iter()
+                    .filter(move |(_, blocked_ip)| {
+                        matches!(blocked_ip.expires_at, Some(expires_at) if expires_at <= now)
+                    })
+                    .map(move |(ip, _)| (app_name.clone(), ip.clone()))
+            })
+            .collect();
+
+        expired.sort();
+        expired
+    }
+
+    pub fn forget_blocked_ip(&mut self, app_name: &str, ip: &str) {
+        if let Some(blocked_ips) = self.blocked_ips.get_mut(app_name) {
+            blocked_ips.remove(ip);
+
+            if blocked_ips.is_empty() {
+                self.blocked_ips.remove(app_name);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Store {
+    pub fn open(path: &str) -> Result<Store, Box<dyn Error>> {
+        let path = PathBuf::from(path);
+
+        let state = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            State::default()
+        };
+
+        Ok(Store {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn read<T>(&self, f: impl FnOnce(&State) -> T) -> T {
+        f(&self.state.lock().unwrap())
+    }
+
+    // Applies a change to the state and writes it to disk
+    // before anyone else can read or change it
+    pub fn update<T>(&self, f: impl FnOnce(&mut State) -> T) -> Result<T, Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state);
+
+        // Write to a temporary file first so a crash
+        // never leaves a half written state file behind
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&*state)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(result)
+    }
+
+    pub fn record(
+        &self,
+        user: &str,
+        app: &str,
+        action: &str,
+        details: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let entry = AuditEntry {
+            at: Utc::now(),
+            user: user.to_string(),
+            app: app.to_string(),
+            action: action.to_string(),
+            details: details.to_string(),
+        };
+
+        self.update(|state| state.audit_log.push(entry))
+    }
+}
+
+impl TypeMapKey for Store {
+    type Value = Arc<Store>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_store_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("ops-bot-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn records_survive_reopening_the_store() {
+        let path = test_store_path("audit");
+
+        let store = Store::open(&path).unwrap();
+        store
+            .record(
+                "someone",
+                "my_app",
+                "deploy_app --force",
+                "CI checks failing: test",
+            )
+            .unwrap();
+
+        let reopened = Store::open(&path).unwrap();
+        let actions: Vec<String> =
+            reopened.read(|state| state.audit_log.iter().map(|e| e.action.clone()).collect());
+
+        assert_eq!(actions, vec!["deploy_app --force"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn find_expired_ip_blocks() {
+        let blocked_ip = |expires_at: Option<&str>| BlockedIp {
+            blocked_by: "someone#1234".to_string(),
+            blocked_at: "2020-05-04T12:00:00Z".parse().unwrap(),
+            expires_at: expires_at.map(|at| at.parse().unwrap()),
+            channel_id: None,
+            reason: None,
+        };
+
+        let mut state = State::default();
+        let my_app = state.blocked_ips.entry("my_app".to_string()).or_default();
+        my_app.insert(
+            "1.2.3.4".to_string(),
+            blocked_ip(Some("2020-05-05T12:00:00Z")),
+        );
+        my_app.insert("5.6.7.0/24".to_string(), blocked_ip(None));
+        state
+            .blocked_ips
+            .entry("other_app".to_string())
+            .or_default()
+            .insert(
+                "9.9.9.9".to_string(),
+                blocked_ip(Some("2020-05-04T13:00:00Z")),
+            );
+
+        let now = "2020-05-05T12:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            state.expired_ip_blocks(now),
+            vec![
+                ("my_app".to_string(), "1.2.3.4".to_string()),
+                ("other_app".to_string(), "9.9.9.9".to_string()),
+            ]
+        );
+
+        state.forget_blocked_ip("other_app", "9.9.9.9");
+        assert!(!state.blocked_ips.contains_key("other_app"));
+        assert_eq!(state.expired_ip_blocks(now).len(), 1);
+    }
+}
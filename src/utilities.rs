@@ -1,4 +1,10 @@
-use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+// Discord refuses messages longer than this
+pub const DISCORD_MESSAGE_LIMIT: usize = 2000;
 
 pub fn parse_config_value_set(config_value: String) -> HashSet<String> {
     config_value.split(',').map(String::from).collect()
@@ -9,6 +15,171 @@ pub fn parse_config_value_string(config_value: HashSet<String>) -> String {
     non_empty.join(",")
 }
 
+// Removes a flag like "--force" from a list of command
+// arguments, returning whether it was present
+pub fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != before
+}
+
+// Removes every occurrence of a flag that takes a value, like
+// "--buildpack heroku/nodejs", returning the values in order
+pub fn take_flag_values(args: &mut Vec<String>, flag: &str) -> Result<Vec<String>, String> {
+    let mut values = Vec::new();
+
+    while let Some(index) = args.iter().position(|arg| arg == flag) {
+        if index + 1 >= args.len() {
+            return Err(format!("{} must be followed by a value", flag));
+        }
+
+        values.push(args.remove(index + 1));
+        args.remove(index);
+    }
+
+    Ok(values)
+}
+
+// Removes a flag that takes a single value, like "--limit 10"
+pub fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    Ok(take_flag_values(args, flag)?.pop())
+}
+
+// Parses durations like "30s", "10m", "24h", "7d" or "1h30m"
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration: {} (try something like 30s, 10m, 24h or 7d)",
+            value
+        )
+    };
+
+    let mut total_seconds = 0;
+    let mut number = String::new();
+
+    for character in value.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+
+        let unit_seconds = match character {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+
+        let amount = number.parse::<u64>().map_err(|_| invalid())?;
+        total_seconds = amount
+            .checked_mul(unit_seconds)
+            .and_then(|seconds| seconds.checked_add(total_seconds))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+
+    if !number.is_empty() || total_seconds == 0 {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(total_seconds))
+}
+
+// Writes a duration the way parse_duration reads it, like "1h30m" or "45s"
+pub fn format_duration(duration: Duration) -> String {
+    let mut seconds = duration.as_secs();
+
+    if seconds == 0 {
+        return String::from("0s");
+    }
+
+    let mut formatted = String::new();
+
+    for &(unit, unit_seconds) in &[("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)] {
+        if seconds >= unit_seconds {
+            formatted.push_str(&format!("{}{}", seconds / unit_seconds, unit));
+            seconds %= unit_seconds;
+        }
+    }
+
+    formatted
+}
+
+// Describes how long ago something happened, like "3 hours ago"
+pub fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - time).num_seconds();
+
+    let (amount, unit) = match seconds {
+        s if s < 60 => return String::from("just now"),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 24 * 60 * 60 => (s / (60 * 60), "hour"),
+        s => (s / (24 * 60 * 60), "day"),
+    };
+
+    if amount == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", amount, unit)
+    }
+}
+
+// Splits a long response into messages Discord will accept,
+// breaking between lines wherever possible
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
+
+    for line in text.split_inclusive('\n') {
+        let mut line = line;
+
+        while current.len() + line.len() > limit {
+            if !current.is_empty() {
+                messages.push(std::mem::take(&mut current));
+                continue;
+            }
+
+            let mut split_at = limit;
+            while !line.is_char_boundary(split_at) {
+                split_at -= 1;
+            }
+
+            messages.push(line[..split_at].to_string());
+            line = &line[split_at..];
+        }
+
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        messages.push(current);
+    }
+
+    messages
+}
+
+// Parses per-app settings like "my_app=value;other_app=other value"
+pub fn parse_app_settings(value: &str) -> Result<HashMap<String, String>, String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|setting| !setting.is_empty())
+        .map(|setting| {
+            let mut parts = setting.splitn(2, '=');
+            let app_name = parts.next().unwrap_or_default().trim();
+            let app_value = parts.next().map(str::trim);
+
+            match app_value {
+                Some(app_value) if !app_name.is_empty() => {
+                    Ok((app_name.to_string(), app_value.to_string()))
+                }
+                _ => Err(format!("Invalid app setting: {}", setting)),
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,4 +206,101 @@ mod tests {
         assert!(ips_string.contains("456.0.0.1"));
         assert!(ips_string.contains("789.0.0.1"));
     }
+
+    #[test]
+    fn take_flag_from_args() {
+        let mut args = vec![
+            "my_app".to_string(),
+            "--force".to_string(),
+            "master".to_string(),
+        ];
+
+        assert!(take_flag(&mut args, "--force"));
+        assert_eq!(args, vec!["my_app", "master"]);
+        assert!(!take_flag(&mut args, "--force"));
+    }
+
+    #[test]
+    fn take_flag_values_from_args() {
+        let mut args = vec![
+            "my_app".to_string(),
+            "--buildpack".to_string(),
+            "heroku/nodejs".to_string(),
+            "master".to_string(),
+            "--buildpack".to_string(),
+            "emk/rust".to_string(),
+        ];
+
+        assert_eq!(
+            take_flag_values(&mut args, "--buildpack").unwrap(),
+            vec!["heroku/nodejs", "emk/rust"]
+        );
+        assert_eq!(args, vec!["my_app", "master"]);
+
+        let mut args = vec!["my_app".to_string(), "--buildpack".to_string()];
+        assert!(take_flag_values(&mut args, "--buildpack").is_err());
+    }
+
+    #[test]
+    fn parse_per_app_settings() {
+        let settings = parse_app_settings("my_app=heroku/nodejs,emk/rust; other_app = 5").unwrap();
+
+        assert_eq!(settings["my_app"], "heroku/nodejs,emk/rust");
+        assert_eq!(settings["other_app"], "5");
+        assert!(parse_app_settings("").unwrap().is_empty());
+        assert!(parse_app_settings("my_app").is_err());
+        assert!(parse_app_settings("=5").is_err());
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+        assert!(parse_duration("ten minutes").is_err());
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(Duration::from_secs(30)), "30s");
+        assert_eq!(format_duration(Duration::from_secs(600)), "10m");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format_duration(Duration::from_secs(90061)), "1d1h1m1s");
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+    }
+
+    #[test]
+    fn describe_relative_times() {
+        let now: DateTime<Utc> = "2020-05-04T12:00:00Z".parse().unwrap();
+        let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+
+        assert_eq!(relative_time(at("2020-05-04T11:59:30Z"), now), "just now");
+        assert_eq!(
+            relative_time(at("2020-05-04T11:59:00Z"), now),
+            "1 minute ago"
+        );
+        assert_eq!(
+            relative_time(at("2020-05-04T09:00:00Z"), now),
+            "3 hours ago"
+        );
+        assert_eq!(relative_time(at("2020-05-01T12:00:00Z"), now), "3 days ago");
+    }
+
+    #[test]
+    fn split_long_messages_between_lines() {
+        let messages = split_message("aaaa\nbbbb\ncc\n", 10);
+        assert_eq!(messages, vec!["aaaa\nbbbb\n", "cc\n"]);
+
+        let messages = split_message("aaaaaaaaaaaaaaa", 10);
+        assert_eq!(messages, vec!["aaaaaaaaaa", "aaaaa"]);
+
+        assert!(split_message("", 10).is_empty());
+    }
 }